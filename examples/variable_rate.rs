@@ -0,0 +1,26 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use simple_video_encoder::VariableRateEncoder;
+
+fn main() {
+    let mut encoder = VariableRateEncoder::new("test_variable_rate.mp4", 256, 256)
+        .expect("Failed to create encoder");
+    let mut frame = encoder.new_frame().unwrap();
+
+    // Simulate a screen capture where frames arrive irregularly, between 10 and 100 ms apart
+    for i in 0..50u32 {
+        frame
+            .fill_with(|x, y| [(x / 2) as u8, (i * 5) as u8, (y / 2) as u8])
+            .unwrap();
+        encoder
+            .append_frame_at_instant(&mut frame, Instant::now())
+            .unwrap();
+
+        thread::sleep(Duration::from_millis(10 + (i as u64 * 37) % 90));
+    }
+
+    encoder.finish().unwrap();
+}
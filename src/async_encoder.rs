@@ -0,0 +1,163 @@
+use std::{
+    error::Error,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+use crate::{Frame, PixelFormat, SimpleVideoEncoder};
+
+#[derive(Default)]
+struct FinishState {
+    result: Option<Result<(), String>>,
+    waker: Option<Waker>,
+}
+
+/// Wraps a [`SimpleVideoEncoder`], moving all encoding work onto a background thread.
+///
+/// Frames are handed to the worker thread through a bounded queue, so producing frames
+/// faster than they can be encoded will eventually apply backpressure instead of
+/// buffering without limit. Create one with [`SimpleVideoEncoder::into_async`].
+pub struct AsyncEncoder {
+    width: i32,
+    height: i32,
+    // the format of frames from new_frame, taken from the wrapped encoder
+    format: PixelFormat,
+
+    sender: SyncSender<Frame>,
+    state: Arc<Mutex<FinishState>>,
+    worker: JoinHandle<()>,
+}
+impl AsyncEncoder {
+    pub(crate) fn new(encoder: SimpleVideoEncoder, queue_size: usize) -> Self {
+        let width = encoder.width;
+        let height = encoder.height;
+        let format = encoder.new_frame_format();
+
+        let (sender, receiver) = mpsc::sync_channel(queue_size);
+        let state = Arc::new(Mutex::new(FinishState::default()));
+
+        let worker_state = Arc::clone(&state);
+        let worker = thread::spawn(move || {
+            // Box<dyn Error> can't cross threads, so only the message is kept. A panic is
+            // reported too, or an awaited FinishHandle would never resolve.
+            let result = panic::catch_unwind(AssertUnwindSafe(|| Self::run(encoder, receiver)))
+                .unwrap_or_else(|_| Err("Encoder thread panicked".into()))
+                .map_err(|e| e.to_string());
+
+            // The lock is never held while anything can panic, so it can't be poisoned.
+            let mut state = worker_state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+
+        Self {
+            width,
+            height,
+            format,
+            sender,
+            state,
+            worker,
+        }
+    }
+
+    fn run(
+        mut encoder: SimpleVideoEncoder,
+        receiver: Receiver<Frame>,
+    ) -> Result<(), Box<dyn Error>> {
+        for mut frame in receiver {
+            encoder.append_frame(&mut frame)?;
+        }
+        encoder.finish()
+    }
+
+    /// Creates a new frame buffer which can be filled with your data and then given to
+    /// [`Self::send_frame`]. Since frames are moved to the worker thread, a new frame is
+    /// needed for each call. Frames have the same format as those from
+    /// [`SimpleVideoEncoder::new_frame`].
+    pub fn new_frame(&self) -> Result<Frame, Box<dyn Error>> {
+        Frame::new(self.format, self.width, self.height)
+    }
+
+    /// Queues the frame to be encoded as the video's next frame.
+    ///
+    /// This returns immediately unless the queue is full, in which case it blocks until
+    /// the worker thread has caught up. If the worker thread has stopped because of an
+    /// error, this returns an error; the original error is reported by [`Self::finish`].
+    pub fn send_frame(&self, frame: Frame) -> Result<(), Box<dyn Error>> {
+        self.sender
+            .send(frame)
+            .map_err(|_| "Encoder thread has stopped; call finish() to get the error".into())
+    }
+
+    /// Like [`Self::send_frame`], but never blocks. If the queue is full, the frame is
+    /// handed back as `Ok(Some(frame))` so it can be retried later.
+    pub fn try_send_frame(&self, frame: Frame) -> Result<Option<Frame>, Box<dyn Error>> {
+        match self.sender.try_send(frame) {
+            Ok(()) => Ok(None),
+            Err(TrySendError::Full(frame)) => Ok(Some(frame)),
+            Err(TrySendError::Disconnected(_)) => {
+                Err("Encoder thread has stopped; call finish() to get the error".into())
+            }
+        }
+    }
+
+    /// Stops accepting frames and lets the worker thread encode everything still queued,
+    /// then finish the video. The returned handle can either be awaited or joined.
+    pub fn finish(self) -> FinishHandle {
+        // Dropping the sender closes the queue, which ends the worker's loop.
+        drop(self.sender);
+
+        FinishHandle {
+            state: self.state,
+            worker: Some(self.worker),
+        }
+    }
+}
+
+/// Returned by [`AsyncEncoder::finish`]. Resolves once the background thread has finished
+/// writing the video.
+///
+/// This implements [`Future`], but does not depend on any particular async runtime.
+/// Synchronous code can use [`Self::join`] instead.
+pub struct FinishHandle {
+    state: Arc<Mutex<FinishState>>,
+    worker: Option<JoinHandle<()>>,
+}
+impl FinishHandle {
+    /// Blocks the current thread until the video has been finished.
+    pub fn join(mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                return Err("Encoder thread panicked".into());
+            }
+        }
+
+        match self.state.lock().unwrap().result.take() {
+            Some(result) => result.map_err(Into::into),
+            None => Err("Encoder thread exited without finishing".into()),
+        }
+    }
+}
+impl Future for FinishHandle {
+    type Output = Result<(), Box<dyn Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result.map_err(Into::into)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
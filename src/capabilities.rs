@@ -0,0 +1,219 @@
+use std::{
+    ffi::{c_void, CStr},
+    os::raw::c_char,
+    ptr,
+};
+
+use ffmpeg_sys_next::{
+    av_buffer_unref, av_hwdevice_ctx_create, av_opt_next, av_version_info,
+    avcodec_find_encoder_by_name, AVHWDeviceType, AVOptionType,
+};
+
+use crate::{logging, VideoCodec};
+
+/// A summary of which encoding features are available in the ffmpeg libraries this program
+/// is running with. Useful for only offering the user options which will actually work.
+#[derive(Debug)]
+pub struct EncoderCapabilities {
+    ffmpeg_version: String,
+    codecs: Vec<VideoCodec>,
+    nvenc: bool,
+    vaapi: bool,
+}
+impl EncoderCapabilities {
+    /// Probes the linked ffmpeg libraries for available encoders and hardware devices.
+    ///
+    /// Checking for hardware devices requires briefly opening them, so this may take a
+    /// moment; it's best to call it once and keep the result.
+    pub fn detect() -> Self {
+        // Probing for missing hardware is expected to fail, so don't let libav log about it.
+        logging::quiet_by_default();
+
+        let ffmpeg_version = unsafe { CStr::from_ptr(av_version_info()) }
+            .to_string_lossy()
+            .into_owned();
+
+        let codecs = VideoCodec::ALL
+            .iter()
+            .copied()
+            .filter(|codec| encoder_exists(codec.encoder_name()))
+            .collect();
+
+        Self {
+            ffmpeg_version,
+            codecs,
+            nvenc: encoder_exists("h264_nvenc\0")
+                && hw_device_exists(AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA),
+            vaapi: encoder_exists("h264_vaapi\0")
+                && hw_device_exists(AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI),
+        }
+    }
+
+    /// The version string of the ffmpeg libraries, as reported by `av_version_info`.
+    pub fn ffmpeg_version(&self) -> &str {
+        &self.ffmpeg_version
+    }
+
+    /// All of the codecs for which an encoder is available.
+    pub fn available_codecs(&self) -> &[VideoCodec] {
+        &self.codecs
+    }
+
+    /// Returns true if an encoder for the given codec is available.
+    pub fn is_available(&self, codec: VideoCodec) -> bool {
+        self.codecs.contains(&codec)
+    }
+
+    /// Returns true if ffmpeg was built with NVENC support and an NVIDIA GPU could be opened.
+    pub fn has_nvenc(&self) -> bool {
+        self.nvenc
+    }
+
+    /// Returns true if ffmpeg was built with VAAPI support and a VAAPI device could be opened.
+    pub fn has_vaapi(&self) -> bool {
+        self.vaapi
+    }
+}
+
+/// The kind of value a [`CodecOption`] takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CodecOptionType {
+    /// An integer, or one of the option's named choices.
+    Int,
+    /// A combination of the option's named flags, such as `+a+b`.
+    Flags,
+    /// A floating-point number.
+    Float,
+    /// `true` or `false`, also written as `1` or `0`.
+    Bool,
+    /// Any string. Encoders like libx264 use these to take further options of their own,
+    /// such as `x264-params`.
+    String,
+    /// A fraction, such as `30000/1001`.
+    Rational,
+    /// A list of `key=value` pairs.
+    Dictionary,
+    /// Anything else, such as a pixel format or a duration.
+    Other,
+}
+impl CodecOptionType {
+    fn from_av(option_type: AVOptionType) -> Self {
+        match option_type {
+            AVOptionType::AV_OPT_TYPE_INT
+            | AVOptionType::AV_OPT_TYPE_INT64
+            | AVOptionType::AV_OPT_TYPE_UINT64 => CodecOptionType::Int,
+            AVOptionType::AV_OPT_TYPE_FLAGS => CodecOptionType::Flags,
+            AVOptionType::AV_OPT_TYPE_DOUBLE | AVOptionType::AV_OPT_TYPE_FLOAT => {
+                CodecOptionType::Float
+            }
+            AVOptionType::AV_OPT_TYPE_BOOL => CodecOptionType::Bool,
+            AVOptionType::AV_OPT_TYPE_STRING => CodecOptionType::String,
+            AVOptionType::AV_OPT_TYPE_RATIONAL => CodecOptionType::Rational,
+            AVOptionType::AV_OPT_TYPE_DICT => CodecOptionType::Dictionary,
+            _ => CodecOptionType::Other,
+        }
+    }
+}
+
+/// An option specific to one encoder, as listed by [`VideoCodec::options`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct CodecOption {
+    /// The name ffmpeg knows the option by, such as `preset`.
+    pub name: String,
+    /// A short description of the option. Empty if the encoder doesn't provide one.
+    pub help: String,
+    /// The kind of value the option takes.
+    pub option_type: CodecOptionType,
+    /// The smallest value allowed, for numeric options.
+    pub min: f64,
+    /// The largest value allowed, for numeric options.
+    pub max: f64,
+    /// The names of the values the option may be set to, if it has a fixed set of them,
+    /// such as the profiles an encoder supports.
+    pub choices: Vec<String>,
+}
+
+/// The encoder-specific options of the encoder called `name`, which must be nul-terminated.
+/// Empty if the encoder doesn't exist.
+pub(crate) fn codec_options(name: &str) -> Vec<CodecOption> {
+    let codec = unsafe { avcodec_find_encoder_by_name(name.as_ptr() as *const i8) };
+    if codec.is_null() || unsafe { (*codec).priv_class }.is_null() {
+        return Vec::new();
+    }
+
+    let to_string = |string: *const c_char| {
+        if string.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(string) }
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+
+    // av_opt_next wants an object whose first field points at its AVClass, which the
+    // codec's priv_class field is on its own.
+    let class = unsafe { ptr::addr_of!((*codec).priv_class) } as *const c_void;
+    let mut options = Vec::new();
+    // the named values, and the unit of the options they belong to
+    let mut constants = Vec::new();
+    let mut option = ptr::null();
+    loop {
+        option = unsafe { av_opt_next(class, option) };
+        let Some(av_option) = (unsafe { option.as_ref() }) else {
+            break;
+        };
+
+        if av_option.type_ == AVOptionType::AV_OPT_TYPE_CONST {
+            constants.push((to_string(av_option.unit), to_string(av_option.name)));
+            continue;
+        }
+        options.push((
+            to_string(av_option.unit),
+            CodecOption {
+                name: to_string(av_option.name),
+                help: to_string(av_option.help),
+                option_type: CodecOptionType::from_av(av_option.type_),
+                min: av_option.min,
+                max: av_option.max,
+                choices: Vec::new(),
+            },
+        ));
+    }
+
+    options
+        .into_iter()
+        .map(|(unit, mut option)| {
+            if !unit.is_empty() {
+                option.choices = constants
+                    .iter()
+                    .filter(|(constant_unit, _)| *constant_unit == unit)
+                    .map(|(_, name)| name.clone())
+                    .collect();
+            }
+            option
+        })
+        .collect()
+}
+
+/// `name` must be nul-terminated.
+fn encoder_exists(name: &str) -> bool {
+    !unsafe { avcodec_find_encoder_by_name(name.as_ptr() as *const i8) }.is_null()
+}
+
+fn hw_device_exists(device_type: AVHWDeviceType) -> bool {
+    let mut device_context = ptr::null_mut();
+    let result = unsafe {
+        av_hwdevice_ctx_create(
+            &mut device_context,
+            device_type,
+            ptr::null(),
+            ptr::null_mut(),
+            0,
+        )
+    };
+    unsafe { av_buffer_unref(&mut device_context) };
+    result >= 0
+}
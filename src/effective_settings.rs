@@ -0,0 +1,59 @@
+use ffmpeg_sys_next::AVCodecContext;
+
+/// The settings an encoder actually ended up with, including ffmpeg's and the encoder's
+/// defaults for anything that wasn't set. Returned by
+/// [`SimpleVideoEncoderBuilder::effective_settings`](crate::SimpleVideoEncoderBuilder::effective_settings).
+///
+/// These are read back from ffmpeg after opening the encoder. Some encoders, such as
+/// libx264, resolve parts of their configuration internally without reporting it back, so
+/// a few values may still be ffmpeg's placeholders rather than what the encoder will use.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct EffectiveSettings {
+    /// The target bitrate in bits per second.
+    pub bitrate: i64,
+    /// The maximum number of frames between keyframes.
+    pub gop_size: i32,
+    /// The minimum number of frames between keyframes.
+    pub keyint_min: i32,
+    /// The maximum number of consecutive B-frames.
+    pub max_b_frames: i32,
+    /// The number of frames the decoder has to hold back for reordering.
+    pub reorder_depth: i32,
+    /// The number of reference frames.
+    pub ref_frames: i32,
+    /// The lowest quantizer the encoder may use.
+    pub qmin: i32,
+    /// The highest quantizer the encoder may use.
+    pub qmax: i32,
+    /// The number of threads the encoder uses. `0` means it picks automatically.
+    pub thread_count: i32,
+    /// The codec profile, as ffmpeg numbers it, if known.
+    pub profile: Option<i32>,
+    /// The codec level, as ffmpeg numbers it, if known.
+    pub level: Option<i32>,
+    /// The number of frames the encoder delays its output by. See
+    /// [`SimpleVideoEncoder::encoder_delay`](crate::SimpleVideoEncoder::encoder_delay).
+    pub encoder_delay: i32,
+}
+impl EffectiveSettings {
+    pub(crate) fn from_context(context: &AVCodecContext) -> Self {
+        // ffmpeg uses negative values for an unknown profile or level.
+        let known = |value: i32| (value >= 0).then_some(value);
+
+        Self {
+            bitrate: context.bit_rate,
+            gop_size: context.gop_size,
+            keyint_min: context.keyint_min,
+            max_b_frames: context.max_b_frames,
+            reorder_depth: context.has_b_frames,
+            ref_frames: context.refs,
+            qmin: context.qmin,
+            qmax: context.qmax,
+            thread_count: context.thread_count,
+            profile: known(context.profile),
+            level: known(context.level),
+            encoder_delay: context.delay,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::{error::Error, fmt, path::PathBuf};
+
+/// Errors which callers may want to handle specifically. These are returned boxed, like every
+/// other error from this crate, and can be recovered with [`Box::downcast_ref`]:
+///
+/// ```no_run
+/// # use simple_video_encoder::{EncoderError, SimpleVideoEncoder};
+/// # let mut encoder = SimpleVideoEncoder::new("out.mp4", 256, 256, 30).unwrap();
+/// # let mut frame = encoder.new_frame().unwrap();
+/// if let Err(e) = encoder.append_frame(&mut frame) {
+///     if let Some(EncoderError::DiskFull) = e.downcast_ref::<EncoderError>() {
+///         // ask the user to free up some space
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EncoderError {
+    /// There was not enough space on the disk to write the output. The output file is
+    /// incomplete.
+    DiskFull,
+    /// The directory the output file should be written to does not exist. See
+    /// [`SimpleVideoEncoderBuilder::create_dirs`](crate::SimpleVideoEncoderBuilder::create_dirs).
+    DirectoryMissing(PathBuf),
+    /// The CRF given to
+    /// [`SimpleVideoEncoderBuilder::crf`](crate::SimpleVideoEncoderBuilder::crf) is outside
+    /// the range supported by the codec, or the codec doesn't support CRF at all.
+    InvalidCrf(i64),
+    /// The encoder was used after the video was finished, for example after a failed
+    /// [`SimpleVideoEncoder::reset_to`](crate::SimpleVideoEncoder::reset_to).
+    InvalidState,
+    /// A timestamp grew too large to be represented. This can only happen with very long
+    /// videos, or with a very high framerate used as a timestamp precision.
+    TimestampOverflow,
+}
+impl fmt::Display for EncoderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncoderError::DiskFull => write!(f, "Error writing output: no space left on device"),
+            EncoderError::DirectoryMissing(dir) => {
+                write!(f, "Output directory {} does not exist", dir.display())
+            }
+            EncoderError::InvalidCrf(crf) => {
+                write!(f, "CRF {} is not supported by the selected codec", crf)
+            }
+            EncoderError::InvalidState => write!(f, "The encoder has already been finished"),
+            EncoderError::TimestampOverflow => write!(f, "Timestamp overflowed"),
+        }
+    }
+}
+impl Error for EncoderError {}
+
+/// An error reported by ffmpeg, returned boxed in place of a formatted message when the
+/// `compact-errors` feature is enabled. This avoids looking up and formatting ffmpeg's
+/// description of the error, so nothing is allocated besides the box itself.
+#[cfg(feature = "compact-errors")]
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct AvError {
+    /// What was being done when the error happened, such as "opening video codec".
+    pub action: &'static str,
+    /// The raw ffmpeg error code, which is always negative.
+    pub code: i32,
+}
+#[cfg(feature = "compact-errors")]
+impl fmt::Display for AvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error {}: ffmpeg error code {}", self.action, self.code)
+    }
+}
+#[cfg(feature = "compact-errors")]
+impl Error for AvError {}
@@ -0,0 +1,35 @@
+use crate::RateControl;
+
+/// Roughly estimates the size in bytes of a video encoded with the given settings, without
+/// encoding anything. This is only meant for things like showing the user an approximate
+/// size before they start encoding.
+///
+/// For [`RateControl::Bitrate`], this is simply the bitrate multiplied by the duration. For
+/// the quality-based modes, the actual size depends heavily on the content, so this uses a
+/// heuristic tuned for H.264 at the default preset; expect it to be off by a factor of 2 or
+/// more for very simple or very noisy content.
+pub fn estimate_output_size(
+    width: i32,
+    height: i32,
+    framerate: i32,
+    frame_count: u64,
+    rate_control: RateControl,
+) -> u64 {
+    let duration_seconds = frame_count as f64 / framerate as f64;
+
+    let bits = match rate_control {
+        RateControl::Bitrate(bitrate) => bitrate as f64 * duration_seconds,
+        RateControl::Crf(crf) => quality_based_bits(width, height, frame_count, crf as f64),
+        RateControl::ConstantQp(qp) => quality_based_bits(width, height, frame_count, qp as f64),
+    };
+
+    (bits / 8.0) as u64
+}
+
+/// Estimates the number of bits needed for the given CRF or QP, assuming typical content
+/// needs about 0.1 bits per pixel at CRF 23, and that the bitrate halves for every increase
+/// of 6 in CRF.
+fn quality_based_bits(width: i32, height: i32, frame_count: u64, quality: f64) -> f64 {
+    let bits_per_pixel = 0.1 * 2f64.powf((23.0 - quality) / 6.0);
+    bits_per_pixel * width as f64 * height as f64 * frame_count as f64
+}
@@ -1,17 +1,38 @@
-use std::{error::Error, ptr::NonNull};
+use std::{
+    error::Error,
+    mem,
+    ptr::{self, NonNull},
+    slice,
+};
 
 use ffmpeg_sys_next::{
-    av_frame_alloc, av_frame_free, av_frame_get_buffer, av_frame_make_writable, AVFrame,
-    AVPixelFormat,
+    av_frame_alloc, av_frame_copy, av_frame_free, av_frame_get_buffer, av_frame_make_writable,
+    av_frame_new_side_data, av_frame_remove_side_data, av_image_copy_plane, av_image_get_linesize,
+    av_pix_fmt_count_planes, av_pix_fmt_desc_get, AVContentLightMetadata, AVFrame,
+    AVFrameSideDataType, AVMasteringDisplayMetadata, AVPictureType, AVPixelFormat, AVRational,
+    AVRegionOfInterest, AV_NOPTS_VALUE,
 };
 
-use crate::make_av_error;
+#[cfg(feature = "bytemuck")]
+use crate::PixelLayout;
+use crate::{make_av_error, ContentLightLevel, FieldOrder, MasteringDisplay, PixelFormat};
 
 /// A buffer used to store a frame to be encoded into the video.
 pub struct Frame {
     frame: NonNull<AVFrame>,
 }
 impl Frame {
+    /// Allocates a new frame with the given pixel format and size. The contents of the frame
+    /// are uninitialized.
+    ///
+    /// Frames of any format and size can be passed to
+    /// [`SimpleVideoEncoder::append_frame`](crate::SimpleVideoEncoder::append_frame); they're
+    /// converted to match the video as needed. [`Self::fill_from_raw_planar`] can fill frames
+    /// of any format, while most of the other fill methods require [`PixelFormat::Rgb24`].
+    pub fn new(format: PixelFormat, width: i32, height: i32) -> Result<Self, Box<dyn Error>> {
+        Self::from_av_format(format.as_av(), width, height)
+    }
+
     /// The width of the frame in pixels.
     pub fn width(&self) -> i32 {
         unsafe { self.frame.as_ref().width }
@@ -22,6 +43,49 @@ impl Frame {
         unsafe { self.frame.as_ref().height }
     }
 
+    /// Reallocates the frame's buffer with new dimensions, keeping its pixel format. The
+    /// contents of the frame are lost.
+    ///
+    /// Frames don't need to match the size of the video; frames of a different size are
+    /// scaled when they're encoded. If the new buffer can't be allocated, such as when a
+    /// dimension isn't positive, an error is returned and the frame is left unchanged.
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), Box<dyn Error>> {
+        // The old buffer is only released once the new one has been allocated.
+        *self = Self::from_av_format(self.av_pixel_format(), width, height)?;
+        Ok(())
+    }
+
+    /// Sets the time at which this frame should be shown, counted in frames at the video's
+    /// framerate. For example, at 30 fps, a timestamp of 45 is 1.5 seconds into the video.
+    ///
+    /// This is only used if
+    /// [`SimpleVideoEncoderBuilder::use_frame_pts`](crate::SimpleVideoEncoderBuilder::use_frame_pts)
+    /// is enabled; otherwise the encoder overwrites it with the frame's position in the video.
+    pub fn set_pts(&mut self, pts: i64) {
+        unsafe {
+            self.frame.as_mut().pts = pts;
+        }
+    }
+
+    /// Copies the pixel data of `src` into this frame, which is useful for keeping a snapshot
+    /// of a frame that will be reused. Both frames must have the same pixel format and size.
+    pub fn copy_from(&mut self, src: &Frame) -> Result<(), Box<dyn Error>> {
+        if self.pixel_format() != src.pixel_format() {
+            return Err("Frames must have the same pixel format".into());
+        }
+        if self.width() != src.width() || self.height() != src.height() {
+            return Err("Frames must be the same size".into());
+        }
+        self.ensure_writeable()?;
+
+        let result = unsafe { av_frame_copy(self.frame.as_ptr(), src.as_raw()) };
+        if result < 0 {
+            Err(make_av_error("copying frame", result))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Fills the frame using data from a Cairo ImageSurface.
     ///
     /// Transparency is ignored - but note that Cairo uses premultiplied alpha, so you
@@ -33,6 +97,9 @@ impl Frame {
         &mut self,
         cairo_surface: &cairo::ImageSurface,
     ) -> Result<(), Box<dyn Error>> {
+        if self.pixel_format() != AVPixelFormat::AV_PIX_FMT_RGB24 as i32 {
+            return Err("Filling from a Cairo surface is only supported for RGB24 frames".into());
+        }
         self.ensure_writeable()?;
 
         let width = self.width() as usize;
@@ -48,48 +115,288 @@ impl Frame {
             return Err("Only CAIRO_FORMAT_RGB24 and CAIRO_FORMAT_ARGB32 are supported".into());
         }
 
-        let frame_stride = self.linesize()[0] as usize;
         let cairo_stride = cairo_surface.stride() as usize;
         // TODO: Is it possible for sws_scale to work with the cairo data directly?
         // That could avoid this copy.
         cairo_surface.with_data(|cairo_data| {
-            for y in 0..height {
-                let line_data = &cairo_data[y * cairo_stride..];
-                let base_offset = y * frame_stride;
-                for x in 0..width {
-                    // each pixel is a 32-bit quantity, with the upper 8 bits unused.
-                    // Red, Green, and Blue are stored in the remaining 24 bits in that order.
-                    // https://www.cairographics.org/manual-1.2.0/cairo-Image-Surfaces.html
-                    let (r, g, b) = if cfg!(target_endian = "big") {
-                        (
-                            // line_data[x * 4 + 0], // alpha
-                            line_data[x * 4 + 1],
-                            line_data[x * 4 + 2],
-                            line_data[x * 4 + 3],
-                        )
-                    } else {
-                        (
-                            // line_data[x * 4 + 3], // alpha
-                            line_data[x * 4 + 2],
-                            line_data[x * 4 + 1],
-                            line_data[x * 4],
-                        )
-                    };
-
-                    let base_offset = base_offset + (3 * x);
-
-                    unsafe {
-                        *self.frame.as_mut().data[0].add(base_offset) = r;
-                        *self.frame.as_mut().data[0].add(base_offset + 1) = g;
-                        *self.frame.as_mut().data[0].add(base_offset + 2) = b;
-                    }
+            self.copy_cairo_rows(cairo_data, cairo_stride, width, height);
+        })?;
+
+        Ok(())
+    }
+
+    /// Copies `width`x`height` pixels of Cairo Rgb24/ARgb32 data into the top-left corner of
+    /// this frame, which must be RGB24.
+    #[cfg(feature = "cairo-input")]
+    fn copy_cairo_rows(
+        &mut self,
+        cairo_data: &[u8],
+        cairo_stride: usize,
+        width: usize,
+        height: usize,
+    ) {
+        let frame_stride = self.linesize()[0] as usize;
+        for y in 0..height {
+            let line_data = &cairo_data[y * cairo_stride..];
+            let base_offset = y * frame_stride;
+            for x in 0..width {
+                // each pixel is a 32-bit quantity, with the upper 8 bits unused.
+                // Red, Green, and Blue are stored in the remaining 24 bits in that order.
+                // https://www.cairographics.org/manual-1.2.0/cairo-Image-Surfaces.html
+                let (r, g, b) = if cfg!(target_endian = "big") {
+                    (
+                        // line_data[x * 4 + 0], // alpha
+                        line_data[x * 4 + 1],
+                        line_data[x * 4 + 2],
+                        line_data[x * 4 + 3],
+                    )
+                } else {
+                    (
+                        // line_data[x * 4 + 3], // alpha
+                        line_data[x * 4 + 2],
+                        line_data[x * 4 + 1],
+                        line_data[x * 4],
+                    )
+                };
+
+                let base_offset = base_offset + (3 * x);
+
+                unsafe {
+                    *self.frame.as_mut().data[0].add(base_offset) = r;
+                    *self.frame.as_mut().data[0].add(base_offset + 1) = g;
+                    *self.frame.as_mut().data[0].add(base_offset + 2) = b;
                 }
             }
+        }
+    }
+
+    /// Draws `text` as white-on-black in the top-left corner of the frame, which must be RGB24.
+    #[cfg(feature = "cairo-input")]
+    pub(crate) fn burn_in_text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        if self.pixel_format() != AVPixelFormat::AV_PIX_FMT_RGB24 as i32 {
+            return Err("Burning in text is only supported for RGB24 frames".into());
+        }
+        self.ensure_writeable()?;
+
+        let box_height = (self.height() / 20).max(16).min(self.height());
+        let font_size = box_height as f64 * 0.75;
+        let padding = box_height as f64 / 4.0;
+
+        let text_width = {
+            // A scratch surface is needed just to measure the text.
+            let scratch = cairo::ImageSurface::create(cairo::Format::Rgb24, 1, 1)?;
+            let context = cairo::Context::new(&scratch)?;
+            context.select_font_face(
+                "monospace",
+                cairo::FontSlant::Normal,
+                cairo::FontWeight::Bold,
+            );
+            context.set_font_size(font_size);
+            context.text_extents(text)?.x_advance()
+        };
+        let box_width = ((text_width + 2.0 * padding).ceil() as i32).min(self.width());
+
+        let surface = cairo::ImageSurface::create(cairo::Format::Rgb24, box_width, box_height)?;
+        {
+            let context = cairo::Context::new(&surface)?;
+            context.set_source_rgb(0.0, 0.0, 0.0);
+            context.paint()?;
+
+            context.select_font_face(
+                "monospace",
+                cairo::FontSlant::Normal,
+                cairo::FontWeight::Bold,
+            );
+            context.set_font_size(font_size);
+            context.set_source_rgb(1.0, 1.0, 1.0);
+            context.move_to(padding, box_height as f64 - padding);
+            context.show_text(text)?;
+        }
+
+        let stride = surface.stride() as usize;
+        surface.with_data(|data| {
+            self.copy_cairo_rows(data, stride, box_width as usize, box_height as usize);
         })?;
 
         Ok(())
     }
 
+    /// Fills the frame by calling `f(x, y)` for every pixel, which returns that pixel's RGB
+    /// value. This writes directly into the frame, without an intermediate buffer.
+    pub fn fill_with(&mut self, f: impl Fn(u32, u32) -> [u8; 3]) -> Result<(), Box<dyn Error>> {
+        if self.pixel_format() != AVPixelFormat::AV_PIX_FMT_RGB24 as i32 {
+            return Err("Filling with a closure is only supported for RGB24 frames".into());
+        }
+        self.ensure_writeable()?;
+
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+
+        let frame_stride = self.linesize()[0] as usize;
+        for y in 0..height {
+            let base_offset = y * frame_stride;
+            for x in 0..width {
+                let base_offset = base_offset + (3 * x);
+                let [r, g, b] = f(x as u32, y as u32);
+
+                unsafe {
+                    *self.frame.as_mut().data[0].add(base_offset) = r;
+                    *self.frame.as_mut().data[0].add(base_offset + 1) = g;
+                    *self.frame.as_mut().data[0].add(base_offset + 2) = b;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills the frame using tightly-packed BGRA data whose rows may be padded, such as a
+    /// texture read back from the GPU. WebGPU and D3D12 both require 256-byte row alignment
+    /// for readbacks, so `bytes_per_row` will often be larger than `4 * width`.
+    ///
+    /// The alpha channel is ignored.
+    pub fn fill_from_padded_bgra(
+        &mut self,
+        data: &[u8],
+        bytes_per_row: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.pixel_format() != AVPixelFormat::AV_PIX_FMT_RGB24 as i32 {
+            return Err("Filling from BGRA data is only supported for RGB24 frames".into());
+        }
+        self.ensure_writeable()?;
+
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+
+        if bytes_per_row < width * 4 {
+            return Err("bytes_per_row is smaller than a row of pixels".into());
+        }
+        if height > 0 && data.len() < bytes_per_row * (height - 1) + width * 4 {
+            return Err("BGRA data is too short for the frame size".into());
+        }
+
+        let frame_stride = self.linesize()[0] as usize;
+        for y in 0..height {
+            let line_data = &data[y * bytes_per_row..];
+            let base_offset = y * frame_stride;
+            for x in 0..width {
+                let base_offset = base_offset + (3 * x);
+
+                unsafe {
+                    *self.frame.as_mut().data[0].add(base_offset) = line_data[x * 4 + 2];
+                    *self.frame.as_mut().data[0].add(base_offset + 1) = line_data[x * 4 + 1];
+                    *self.frame.as_mut().data[0].add(base_offset + 2) = line_data[x * 4];
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills the frame by copying raw image data for each of the planes of the frame's pixel
+    /// format, such as the Y, U, and V planes of a YUV420P frame, or the Y and interleaved UV
+    /// planes of an NV12 frame. Packed formats such as RGB24 have a single plane.
+    ///
+    /// `strides` gives the number of bytes between the starts of consecutive rows in each
+    /// of the source planes, and must have the same length as `planes`. The number of planes
+    /// must match the frame's pixel format.
+    pub fn fill_from_raw_planar(
+        &mut self,
+        planes: &[&[u8]],
+        strides: &[usize],
+    ) -> Result<(), Box<dyn Error>> {
+        self.ensure_writeable()?;
+
+        let plane_sizes = self.plane_sizes()?;
+        if planes.len() != plane_sizes.len() {
+            return Err(format!(
+                "Frame's pixel format has {} planes, but {} were provided",
+                plane_sizes.len(),
+                planes.len()
+            )
+            .into());
+        }
+        if strides.len() != planes.len() {
+            return Err("A stride must be provided for each plane".into());
+        }
+
+        for (i, ((plane, &stride), &(row_bytes, rows))) in
+            planes.iter().zip(strides).zip(&plane_sizes).enumerate()
+        {
+            if stride < row_bytes {
+                return Err(
+                    format!("Stride for plane {} is smaller than a row of pixels", i).into(),
+                );
+            }
+            if rows > 0 && plane.len() < stride * (rows - 1) + row_bytes {
+                return Err(format!("Data for plane {} is too short", i).into());
+            }
+
+            unsafe {
+                av_image_copy_plane(
+                    self.frame.as_mut().data[i],
+                    self.frame.as_ref().linesize[i],
+                    plane.as_ptr(),
+                    stride as i32,
+                    row_bytes as i32,
+                    rows as i32,
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills the frame from a single buffer holding every plane of the frame's pixel format,
+    /// one after another. `stride` is the number of bytes between rows of the first plane;
+    /// the strides of any other planes are scaled to match, as described on
+    /// [`RawFrameFormat`](crate::RawFrameFormat).
+    pub(crate) fn fill_from_raw_contiguous(
+        &mut self,
+        data: &[u8],
+        stride: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        let plane_sizes = self.plane_sizes()?;
+        let first_row_bytes = plane_sizes.first().map_or(0, |&(row_bytes, _)| row_bytes);
+        if stride < first_row_bytes {
+            return Err("Stride is smaller than a row of pixels".into());
+        }
+
+        let mut planes = Vec::with_capacity(plane_sizes.len());
+        let mut strides = Vec::with_capacity(plane_sizes.len());
+        let mut rest = data;
+        for (i, &(row_bytes, rows)) in plane_sizes.iter().enumerate() {
+            // Subsampled chroma planes have proportionally shorter rows, and so proportionally
+            // less padding.
+            let plane_stride = if row_bytes == first_row_bytes {
+                stride
+            } else {
+                (row_bytes * stride).div_ceil(first_row_bytes)
+            };
+            // The last row of the last plane doesn't need its padding.
+            let plane_len = if i + 1 == plane_sizes.len() && rows > 0 {
+                plane_stride * (rows - 1) + row_bytes
+            } else {
+                plane_stride * rows
+            };
+            if rest.len() < plane_len {
+                return Err(format!(
+                    "Raw frame data is too short: plane {} needs {} more bytes than were given",
+                    i,
+                    plane_len - rest.len()
+                )
+                .into());
+            }
+
+            let (plane, next) = rest.split_at(plane_len);
+            planes.push(plane);
+            strides.push(plane_stride);
+            rest = next;
+        }
+
+        self.fill_from_raw_planar(&planes, &strides)
+    }
+
     /// Populates this frame with an image using an RgbImage from the `image` crate.
     ///
     /// If you have a different type of image, it can be converted by using `DynamicImage`.
@@ -97,6 +404,9 @@ impl Frame {
     /// *Only enabled with the `image-input` feature.*
     #[cfg(feature = "image-input")]
     pub fn fill_from_image_rgb(&mut self, image: &image::RgbImage) -> Result<(), Box<dyn Error>> {
+        if self.pixel_format() != AVPixelFormat::AV_PIX_FMT_RGB24 as i32 {
+            return Err("Filling from an image is only supported for RGB24 frames".into());
+        }
         self.ensure_writeable()?;
 
         let width = self.width() as usize;
@@ -123,9 +433,165 @@ impl Frame {
 
         Ok(())
     }
+
+    /// Populates this frame with a region of a larger RgbImage from the `image` crate,
+    /// without scaling it. The region is the size of the frame, with its top-left corner at
+    /// `(x, y)` in the image, and must fit entirely within the image.
+    ///
+    /// *Only enabled with the `image-input` feature.*
+    #[cfg(feature = "image-input")]
+    pub fn fill_from_image_rgb_cropped(
+        &mut self,
+        image: &image::RgbImage,
+        x: u32,
+        y: u32,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.pixel_format() != AVPixelFormat::AV_PIX_FMT_RGB24 as i32 {
+            return Err("Filling from an image is only supported for RGB24 frames".into());
+        }
+        self.ensure_writeable()?;
+
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+
+        if x as usize + width > image.width() as usize
+            || y as usize + height > image.height() as usize
+        {
+            return Err("Crop region does not fit within the image!".into());
+        }
+
+        let frame_stride = self.linesize()[0] as usize;
+        let image_stride = 3 * image.width() as usize;
+        let samples = image.as_raw();
+
+        for row in 0..height {
+            let start = (y as usize + row) * image_stride + 3 * x as usize;
+            let source = &samples[start..start + 3 * width];
+
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    source.as_ptr(),
+                    self.frame.as_mut().data[0].add(row * frame_stride),
+                    source.len(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Populates this frame with the contents of a `Pixmap` from the `tiny-skia` crate. The
+    /// pixmap's colors are un-premultiplied and its alpha channel is dropped.
+    ///
+    /// *Only enabled with the `tiny-skia-input` feature.*
+    #[cfg(feature = "tiny-skia-input")]
+    pub fn fill_from_pixmap(&mut self, pixmap: &tiny_skia::Pixmap) -> Result<(), Box<dyn Error>> {
+        if self.pixel_format() != AVPixelFormat::AV_PIX_FMT_RGB24 as i32 {
+            return Err("Filling from a pixmap is only supported for RGB24 frames".into());
+        }
+        self.ensure_writeable()?;
+
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+
+        if pixmap.width() as usize != width || pixmap.height() as usize != height {
+            return Err("Pixmap does not match frame size!".into());
+        }
+
+        let frame_stride = self.linesize()[0] as usize;
+
+        for (y, row) in pixmap.pixels().chunks_exact(width).enumerate() {
+            let base_offset = y * frame_stride;
+            for (x, pixel) in row.iter().enumerate() {
+                let base_offset = base_offset + (3 * x);
+                let color = pixel.demultiply();
+
+                unsafe {
+                    *self.frame.as_mut().data[0].add(base_offset) = color.red();
+                    *self.frame.as_mut().data[0].add(base_offset + 1) = color.green();
+                    *self.frame.as_mut().data[0].add(base_offset + 2) = color.blue();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Populates this frame from a slice of tightly-packed pixels of any plain-old-data type,
+    /// such as `[f32; 4]` or `u32`, interpreted according to `layout`. The slice is
+    /// reinterpreted as bytes, so for types like `u32` the layout describes the order of
+    /// bytes in memory rather than the value's bits.
+    ///
+    /// The frame must be [`PixelFormat::Rgb24`], [`PixelFormat::Rgba`], or
+    /// [`PixelFormat::Rgb48`]. Alpha is only kept for RGBA frames, and floating-point input
+    /// is best paired with RGB48 frames to keep its precision.
+    ///
+    /// *Only enabled with the `bytemuck` feature.*
+    #[cfg(feature = "bytemuck")]
+    pub fn fill_from_pod<T: bytemuck::Pod>(
+        &mut self,
+        data: &[T],
+        layout: PixelLayout,
+    ) -> Result<(), Box<dyn Error>> {
+        // Bytes per pixel in the frame.
+        let frame_bpp =
+            match self.av_pixel_format() {
+                AVPixelFormat::AV_PIX_FMT_RGB24 => 3,
+                AVPixelFormat::AV_PIX_FMT_RGBA => 4,
+                AVPixelFormat::AV_PIX_FMT_RGB48LE => 6,
+                _ => return Err(
+                    "Filling from pixel data is only supported for RGB24, RGBA, and RGB48 frames"
+                        .into(),
+                ),
+            };
+        self.ensure_writeable()?;
+
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let bpp = layout.bytes_per_pixel();
+        if bytes.len() != width * height * bpp {
+            return Err("Pixel data does not match frame size!".into());
+        }
+
+        let frame_stride = self.linesize()[0] as usize;
+        for (y, row) in bytes.chunks_exact((width * bpp).max(1)).enumerate() {
+            let line = unsafe {
+                slice::from_raw_parts_mut(
+                    self.frame.as_mut().data[0].add(y * frame_stride),
+                    width * frame_bpp,
+                )
+            };
+
+            for (pixel, out) in row.chunks_exact(bpp).zip(line.chunks_exact_mut(frame_bpp)) {
+                let [r, g, b, a] = layout.read_rgba16(pixel);
+                match frame_bpp {
+                    3 => out.copy_from_slice(&[(r >> 8) as u8, (g >> 8) as u8, (b >> 8) as u8]),
+                    4 => out.copy_from_slice(&[
+                        (r >> 8) as u8,
+                        (g >> 8) as u8,
+                        (b >> 8) as u8,
+                        (a >> 8) as u8,
+                    ]),
+                    _ => {
+                        out[0..2].copy_from_slice(&r.to_le_bytes());
+                        out[2..4].copy_from_slice(&g.to_le_bytes());
+                        out[4..6].copy_from_slice(&b.to_le_bytes());
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 impl Frame {
-    pub(crate) fn new(fmt: AVPixelFormat, width: i32, height: i32) -> Result<Self, Box<dyn Error>> {
+    pub(crate) fn from_av_format(
+        fmt: AVPixelFormat,
+        width: i32,
+        height: i32,
+    ) -> Result<Self, Box<dyn Error>> {
         let Some(mut frame) = NonNull::new(unsafe { av_frame_alloc() }) else {
             return Err("Error allocating AVFrame".into());
         };
@@ -136,18 +602,233 @@ impl Frame {
             frame.as_mut().height = height;
         }
 
-        let res = unsafe { av_frame_get_buffer(frame.as_ptr(), 0) };
+        // Wrapping it first means it's freed if the buffer can't be allocated.
+        let frame = Self { frame };
+        let res = unsafe { av_frame_get_buffer(frame.frame.as_ptr(), 0) };
         if res < 0 {
             return Err(make_av_error("allocating frame buffer", res));
         }
 
-        Ok(Self { frame })
+        Ok(frame)
     }
 
     pub(crate) fn pixel_format(&self) -> i32 {
         unsafe { self.frame.as_ref().format }
     }
 
+    pub(crate) fn av_pixel_format(&self) -> AVPixelFormat {
+        unsafe { std::mem::transmute_copy(&self.pixel_format()) }
+    }
+
+    /// Returns the number of bytes in a row of pixels, and the number of rows, for each plane
+    /// of the frame's pixel format.
+    pub(crate) fn plane_sizes(&self) -> Result<Vec<(usize, usize)>, Box<dyn Error>> {
+        let format = self.av_pixel_format();
+        let desc = unsafe { av_pix_fmt_desc_get(format) };
+        if desc.is_null() {
+            return Err("Frame has an unknown pixel format".into());
+        }
+
+        let plane_count = unsafe { av_pix_fmt_count_planes(format) };
+        if plane_count < 0 {
+            return Err(make_av_error("counting planes", plane_count));
+        }
+
+        let log2_chroma_h = unsafe { (*desc).log2_chroma_h };
+        (0..plane_count)
+            .map(|i| {
+                let row_bytes = unsafe { av_image_get_linesize(format, self.width(), i) };
+                if row_bytes < 0 {
+                    return Err(make_av_error("computing plane size", row_bytes));
+                }
+
+                // Planes 1 and 2 are always the chroma planes, which may be vertically subsampled.
+                let rows = if i == 1 || i == 2 {
+                    -((-self.height()) >> log2_chroma_h)
+                } else {
+                    self.height()
+                };
+
+                Ok((row_bytes as usize, rows as usize))
+            })
+            .collect()
+    }
+
+    /// Returns true if both frames have the same format, size, and pixel data.
+    pub(crate) fn same_contents(&self, other: &Frame) -> bool {
+        if self.pixel_format() != other.pixel_format()
+            || self.width() != other.width()
+            || self.height() != other.height()
+        {
+            return false;
+        }
+
+        let Ok(plane_sizes) = self.plane_sizes() else {
+            return false;
+        };
+        plane_sizes
+            .iter()
+            .enumerate()
+            .all(|(i, &(row_bytes, rows))| {
+                (0..rows).all(|y| unsafe {
+                    let a = std::slice::from_raw_parts(
+                        self.data()[i].add(y * self.linesize()[i] as usize),
+                        row_bytes,
+                    );
+                    let b = std::slice::from_raw_parts(
+                        other.data()[i].add(y * other.linesize()[i] as usize),
+                        row_bytes,
+                    );
+                    a == b
+                })
+            })
+    }
+
+    /// Applies a Gaussian blur with a standard deviation of `sigma` samples to each plane of
+    /// the frame, which must have 8-bit samples. Pixels past the edges of the frame are treated
+    /// as copies of the nearest edge pixel.
+    pub(crate) fn blur(&mut self, sigma: f32) -> Result<(), Box<dyn Error>> {
+        // The number of samples interleaved in each plane, such as U and V in NV12's second.
+        let channels: &[usize] = match self.av_pixel_format() {
+            AVPixelFormat::AV_PIX_FMT_RGB24 => &[3],
+            AVPixelFormat::AV_PIX_FMT_RGBA | AVPixelFormat::AV_PIX_FMT_BGRA => &[4],
+            AVPixelFormat::AV_PIX_FMT_YUV420P | AVPixelFormat::AV_PIX_FMT_YUV444P => &[1, 1, 1],
+            AVPixelFormat::AV_PIX_FMT_NV12 => &[1, 2],
+            AVPixelFormat::AV_PIX_FMT_GRAY8 => &[1],
+            _ => return Err("Denoising is only supported for frames with 8-bit samples".into()),
+        };
+        let plane_sizes = self.plane_sizes()?;
+        self.ensure_writeable()?;
+
+        // Beyond three standard deviations, the weights are too small to matter.
+        let radius = (sigma * 3.0).ceil() as isize;
+        let mut kernel: Vec<f32> = (-radius..=radius)
+            .map(|offset| (-(offset * offset) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect();
+        let total: f32 = kernel.iter().sum();
+        kernel.iter_mut().for_each(|weight| *weight /= total);
+
+        for (plane, (&(row_size, height), &channels)) in
+            plane_sizes.iter().zip(channels).enumerate()
+        {
+            let width = row_size / channels;
+            if width == 0 || height == 0 {
+                continue;
+            }
+            let stride = self.linesize()[plane] as usize;
+            let data = unsafe {
+                slice::from_raw_parts_mut(
+                    self.frame.as_mut().data[plane],
+                    stride * (height - 1) + row_size,
+                )
+            };
+
+            // The blur is separable, so blur each row, and then each column of the result.
+            let mut horizontal = vec![0.0f32; row_size * height];
+            for y in 0..height {
+                let row = &data[y * stride..y * stride + row_size];
+                for x in 0..width {
+                    for channel in 0..channels {
+                        horizontal[y * row_size + x * channels + channel] = kernel
+                            .iter()
+                            .zip(-radius..=radius)
+                            .map(|(weight, offset)| {
+                                let sample_x = (x as isize + offset).clamp(0, width as isize - 1);
+                                weight * row[sample_x as usize * channels + channel] as f32
+                            })
+                            .sum();
+                    }
+                }
+            }
+            for y in 0..height {
+                for i in 0..row_size {
+                    let value: f32 = kernel
+                        .iter()
+                        .zip(-radius..=radius)
+                        .map(|(weight, offset)| {
+                            let sample_y = (y as isize + offset).clamp(0, height as isize - 1);
+                            weight * horizontal[sample_y as usize * row_size + i]
+                        })
+                        .sum();
+                    data[y * stride + i] = value.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills this frame with the pixels of `src` multiplied by `factor`, which darkens it when
+    /// `factor` is less than 1. Both frames must be RGB24 and the same size.
+    pub(crate) fn scaled_from(&mut self, src: &Frame, factor: f32) -> Result<(), Box<dyn Error>> {
+        let rgb24 = AVPixelFormat::AV_PIX_FMT_RGB24 as i32;
+        if self.pixel_format() != rgb24 || src.pixel_format() != rgb24 {
+            return Err("Scaling pixel values is only supported for RGB24 frames".into());
+        }
+        if self.width() != src.width() || self.height() != src.height() {
+            return Err("Frames must be the same size".into());
+        }
+        self.ensure_writeable()?;
+
+        let row_size = self.width() as usize * 3;
+        let src_stride = src.linesize()[0] as usize;
+        let dest_stride = self.linesize()[0] as usize;
+        for y in 0..self.height() as usize {
+            for x in 0..row_size {
+                unsafe {
+                    let value = *src.data()[0].add(y * src_stride + x);
+                    *self.frame.as_mut().data[0].add(y * dest_stride + x) =
+                        (value as f32 * factor).round() as u8;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies all of `src` into this frame, with its top left corner at (`x`, `y`). Both frames
+    /// must be RGB24, and `src` must fit inside this frame at that position.
+    pub(crate) fn copy_into_region(
+        &mut self,
+        src: &Frame,
+        x: i32,
+        y: i32,
+    ) -> Result<(), Box<dyn Error>> {
+        let rgb24 = AVPixelFormat::AV_PIX_FMT_RGB24 as i32;
+        if self.pixel_format() != rgb24 || src.pixel_format() != rgb24 {
+            return Err("Compositing is only supported for RGB24 frames".into());
+        }
+        if x < 0 || y < 0 || x + src.width() > self.width() || y + src.height() > self.height() {
+            return Err(format!(
+                "A {}x{} frame doesn't fit inside a {}x{} frame at ({}, {})",
+                src.width(),
+                src.height(),
+                self.width(),
+                self.height(),
+                x,
+                y
+            )
+            .into());
+        }
+        self.ensure_writeable()?;
+
+        let row_size = src.width() as usize * 3;
+        let src_stride = src.linesize()[0] as usize;
+        let dest_stride = self.linesize()[0] as usize;
+        let dest_offset = y as usize * dest_stride + x as usize * 3;
+        for row in 0..src.height() as usize {
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    src.data()[0].add(row * src_stride),
+                    self.frame.as_mut().data[0].add(dest_offset + row * dest_stride),
+                    row_size,
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn ensure_writeable(&mut self) -> Result<(), Box<dyn Error>> {
         let result = unsafe { av_frame_make_writable(self.frame.as_ptr()) };
         if result < 0 {
@@ -157,12 +838,170 @@ impl Frame {
         }
     }
 
-    pub(crate) fn set_pts(&mut self, pts: i64) {
+    /// The frame's presentation timestamp, or `None` if it hasn't been set.
+    pub(crate) fn pts(&self) -> Option<i64> {
+        let pts = unsafe { self.frame.as_ref().pts };
+        (pts != AV_NOPTS_VALUE).then_some(pts)
+    }
+
+    /// Forces the encoder to make this frame a keyframe, or lets it decide if `false`.
+    pub(crate) fn set_keyframe(&mut self, keyframe: bool) {
+        unsafe {
+            self.frame.as_mut().pict_type = if keyframe {
+                AVPictureType::AV_PICTURE_TYPE_I
+            } else {
+                AVPictureType::AV_PICTURE_TYPE_NONE
+            };
+        }
+    }
+
+    /// Marks the frame as interlaced with the given field order, or progressive if `None`.
+    pub(crate) fn set_field_order(&mut self, field_order: Option<FieldOrder>) {
         unsafe {
-            self.frame.as_mut().pts = pts;
+            self.frame.as_mut().interlaced_frame = field_order.is_some() as i32;
+            self.frame.as_mut().top_field_first =
+                matches!(field_order, Some(FieldOrder::TopFieldFirst)) as i32;
         }
     }
 
+    /// Replaces any unregistered SEI side data on the frame with `sei`, or removes it if `None`.
+    pub(crate) fn set_sei_unregistered(
+        &mut self,
+        sei: Option<&[u8]>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.set_side_data(AVFrameSideDataType::AV_FRAME_DATA_SEI_UNREGISTERED, sei)
+    }
+
+    /// Asks the encoder to adjust the quantizer for the whole frame by `offset`, from -1 (best
+    /// quality) to 1 (worst), or removes the adjustment if `None`.
+    pub(crate) fn set_quantizer_offset(
+        &mut self,
+        offset: Option<f32>,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some(offset) = offset else {
+            return self
+                .set_side_data(AVFrameSideDataType::AV_FRAME_DATA_REGIONS_OF_INTEREST, None);
+        };
+
+        // A single region covering the whole frame.
+        let region = AVRegionOfInterest {
+            self_size: mem::size_of::<AVRegionOfInterest>() as u32,
+            top: 0,
+            bottom: self.height(),
+            left: 0,
+            right: self.width(),
+            qoffset: AVRational {
+                num: (offset * 1000.0) as i32,
+                den: 1000,
+            },
+        };
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                &region as *const AVRegionOfInterest as *const u8,
+                mem::size_of::<AVRegionOfInterest>(),
+            )
+        };
+        self.set_side_data(
+            AVFrameSideDataType::AV_FRAME_DATA_REGIONS_OF_INTEREST,
+            Some(bytes),
+        )
+    }
+
+    /// Attaches HDR10 mastering display and content light level metadata to the frame, or
+    /// removes it if `None`.
+    pub(crate) fn set_hdr10_metadata(
+        &mut self,
+        hdr10: Option<(MasteringDisplay, ContentLightLevel)>,
+    ) -> Result<(), Box<dyn Error>> {
+        let Some((mastering, light_level)) = hdr10 else {
+            self.set_side_data(
+                AVFrameSideDataType::AV_FRAME_DATA_MASTERING_DISPLAY_METADATA,
+                None,
+            )?;
+            return self
+                .set_side_data(AVFrameSideDataType::AV_FRAME_DATA_CONTENT_LIGHT_LEVEL, None);
+        };
+
+        // These are the units HEVC uses to signal the metadata, so no precision is lost.
+        let chromaticity = |(x, y): (f64, f64)| {
+            [
+                AVRational {
+                    num: (x * 50000.0).round() as i32,
+                    den: 50000,
+                },
+                AVRational {
+                    num: (y * 50000.0).round() as i32,
+                    den: 50000,
+                },
+            ]
+        };
+        let luminance = |nits: f64| AVRational {
+            num: (nits * 10000.0).round() as i32,
+            den: 10000,
+        };
+
+        let mastering = AVMasteringDisplayMetadata {
+            display_primaries: [
+                chromaticity(mastering.red),
+                chromaticity(mastering.green),
+                chromaticity(mastering.blue),
+            ],
+            white_point: chromaticity(mastering.white_point),
+            min_luminance: luminance(mastering.min_luminance),
+            max_luminance: luminance(mastering.max_luminance),
+            has_primaries: 1,
+            has_luminance: 1,
+        };
+        let light_level = AVContentLightMetadata {
+            MaxCLL: light_level.max_cll,
+            MaxFALL: light_level.max_fall,
+        };
+
+        let mastering_bytes = unsafe {
+            slice::from_raw_parts(
+                &mastering as *const AVMasteringDisplayMetadata as *const u8,
+                mem::size_of::<AVMasteringDisplayMetadata>(),
+            )
+        };
+        let light_level_bytes = unsafe {
+            slice::from_raw_parts(
+                &light_level as *const AVContentLightMetadata as *const u8,
+                mem::size_of::<AVContentLightMetadata>(),
+            )
+        };
+        self.set_side_data(
+            AVFrameSideDataType::AV_FRAME_DATA_MASTERING_DISPLAY_METADATA,
+            Some(mastering_bytes),
+        )?;
+        self.set_side_data(
+            AVFrameSideDataType::AV_FRAME_DATA_CONTENT_LIGHT_LEVEL,
+            Some(light_level_bytes),
+        )
+    }
+
+    fn set_side_data(
+        &mut self,
+        kind: AVFrameSideDataType,
+        data: Option<&[u8]>,
+    ) -> Result<(), Box<dyn Error>> {
+        unsafe {
+            av_frame_remove_side_data(self.frame.as_ptr(), kind);
+        }
+
+        if let Some(data) = data {
+            let side_data =
+                unsafe { av_frame_new_side_data(self.frame.as_ptr(), kind, data.len() as _) };
+            if side_data.is_null() {
+                return Err("Error allocating frame side data".into());
+            }
+            unsafe {
+                ptr::copy_nonoverlapping(data.as_ptr(), (*side_data).data, data.len());
+            }
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn data(&self) -> &[*const u8] {
         unsafe {
             std::slice::from_raw_parts(
@@ -185,6 +1024,9 @@ impl Frame {
         self.frame.as_ptr()
     }
 }
+// Safety: the AVFrame is owned exclusively by this object, and ffmpeg's buffer reference
+// counting is thread-safe, so it can be moved to another thread.
+unsafe impl Send for Frame {}
 impl Drop for Frame {
     fn drop(&mut self) {
         let frame_ptr = std::mem::replace(&mut self.frame, NonNull::dangling());
@@ -192,3 +1034,166 @@ impl Drop for Frame {
         unsafe { av_frame_free(&mut raw_frame_ptr) };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the first `len` bytes of row `y` of `plane`.
+    fn row(frame: &Frame, plane: usize, y: usize, len: usize) -> Vec<u8> {
+        unsafe {
+            slice::from_raw_parts(
+                frame.data()[plane].add(y * frame.linesize()[plane] as usize),
+                len,
+            )
+        }
+        .to_vec()
+    }
+
+    #[test]
+    fn fill_from_raw_planar_copies_each_plane() {
+        let mut frame = Frame::new(PixelFormat::Yuv420p, 4, 2).unwrap();
+        // The luma plane has two bytes of padding after each row.
+        let y = [1, 2, 3, 4, 0, 0, 5, 6, 7, 8];
+        frame
+            .fill_from_raw_planar(&[&y, &[9, 10], &[11, 12]], &[6, 2, 2])
+            .unwrap();
+        assert_eq!(row(&frame, 0, 0, 4), [1, 2, 3, 4]);
+        assert_eq!(row(&frame, 0, 1, 4), [5, 6, 7, 8]);
+        assert_eq!(row(&frame, 1, 0, 2), [9, 10]);
+        assert_eq!(row(&frame, 2, 0, 2), [11, 12]);
+    }
+
+    #[test]
+    fn fill_from_raw_planar_checks_sizes() {
+        let mut frame = Frame::new(PixelFormat::Yuv420p, 4, 2).unwrap();
+        let y = [0; 8];
+        let chroma = [0; 2];
+        // wrong number of planes
+        assert!(frame.fill_from_raw_planar(&[&y, &chroma], &[4, 2]).is_err());
+        // wrong number of strides
+        assert!(frame
+            .fill_from_raw_planar(&[&y, &chroma, &chroma], &[4, 2])
+            .is_err());
+        // stride shorter than a row
+        assert!(frame
+            .fill_from_raw_planar(&[&y, &chroma, &chroma], &[3, 2, 2])
+            .is_err());
+        // plane shorter than its rows
+        assert!(frame
+            .fill_from_raw_planar(&[&y[..7], &chroma, &chroma], &[4, 2, 2])
+            .is_err());
+    }
+
+    #[test]
+    fn same_contents_compares_pixels() {
+        let mut a = Frame::new(PixelFormat::Rgb24, 5, 3).unwrap();
+        let mut b = Frame::new(PixelFormat::Rgb24, 5, 3).unwrap();
+        a.fill_with(|x, y| [x as u8, y as u8, 0]).unwrap();
+        b.fill_with(|x, y| [x as u8, y as u8, 0]).unwrap();
+        assert!(a.same_contents(&b));
+
+        b.fill_with(|x, y| [x as u8, y as u8, (x == 4 && y == 2) as u8])
+            .unwrap();
+        assert!(!a.same_contents(&b));
+
+        let gray = Frame::new(PixelFormat::Gray8, 5, 3).unwrap();
+        assert!(!a.same_contents(&gray));
+        let smaller = Frame::new(PixelFormat::Rgb24, 5, 2).unwrap();
+        assert!(!a.same_contents(&smaller));
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn fill_from_pod_converts_layouts() {
+        let mut frame = Frame::new(PixelFormat::Rgb24, 2, 1).unwrap();
+        frame
+            .fill_from_pod(&[[1u8, 2, 3, 4], [5, 6, 7, 8]], PixelLayout::Bgra8)
+            .unwrap();
+        assert_eq!(row(&frame, 0, 0, 6), [3, 2, 1, 7, 6, 5]);
+
+        let mut frame = Frame::new(PixelFormat::Rgba, 2, 1).unwrap();
+        frame
+            .fill_from_pod(&[[0.0f32, 0.5, 1.0, 2.0]; 2], PixelLayout::RgbaF32)
+            .unwrap();
+        assert_eq!(row(&frame, 0, 0, 4), [0, 128, 255, 255]);
+
+        let mut frame = Frame::new(PixelFormat::Rgb48, 1, 1).unwrap();
+        frame
+            .fill_from_pod(&[[255u8, 0, 1]], PixelLayout::Rgb8)
+            .unwrap();
+        assert_eq!(row(&frame, 0, 0, 6), [255, 255, 0, 0, 1, 1]);
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn fill_from_pod_checks_format_and_size() {
+        let mut frame = Frame::new(PixelFormat::Rgb24, 2, 2).unwrap();
+        assert!(frame.fill_from_pod(&[0u32; 3], PixelLayout::Rgba8).is_err());
+        assert!(frame.fill_from_pod(&[0u32; 5], PixelLayout::Rgba8).is_err());
+
+        let mut frame = Frame::new(PixelFormat::Yuv420p, 2, 2).unwrap();
+        assert!(frame.fill_from_pod(&[0u32; 4], PixelLayout::Rgba8).is_err());
+    }
+
+    #[test]
+    fn blur_keeps_flat_planes_flat() {
+        let mut frame = Frame::new(PixelFormat::Yuv420p, 6, 4).unwrap();
+        let y = [100; 24];
+        frame
+            .fill_from_raw_planar(&[&y, &[50; 6], &[200; 6]], &[6, 3, 3])
+            .unwrap();
+        frame.blur(1.0).unwrap();
+        assert_eq!(row(&frame, 0, 3, 6), [100; 6]);
+        assert_eq!(row(&frame, 1, 1, 3), [50; 3]);
+        assert_eq!(row(&frame, 2, 1, 3), [200; 3]);
+    }
+
+    #[test]
+    fn blur_spreads_each_channel_separately() {
+        let mut frame = Frame::new(PixelFormat::Rgba, 5, 1).unwrap();
+        let mut pixels = [0; 20];
+        // a single red pixel in the middle, on an opaque background
+        pixels[8] = 255;
+        pixels.iter_mut().skip(3).step_by(4).for_each(|a| *a = 255);
+        frame.fill_from_raw_planar(&[&pixels], &[20]).unwrap();
+        frame.blur(1.0).unwrap();
+
+        let blurred = row(&frame, 0, 0, 20);
+        let red: Vec<u8> = blurred.iter().step_by(4).copied().collect();
+        assert!(red[2] < 255 && red[1] > 0 && red[3] > 0);
+        assert_eq!(red[1], red[3]);
+        assert!(blurred.iter().skip(1).step_by(4).all(|&g| g == 0));
+        assert!(blurred.iter().skip(3).step_by(4).all(|&a| a == 255));
+    }
+
+    #[test]
+    fn blur_rejects_high_bit_depth_frames() {
+        let mut frame = Frame::new(PixelFormat::Rgb48, 4, 4).unwrap();
+        assert!(frame.blur(1.0).is_err());
+    }
+
+    #[test]
+    fn rgb_fills_reject_other_formats() {
+        for format in [PixelFormat::Gray8, PixelFormat::Yuv420p, PixelFormat::Rgba] {
+            let mut frame = Frame::new(format, 4, 4).unwrap();
+            assert!(frame.fill_with(|_, _| [1, 2, 3]).is_err());
+            assert!(frame.fill_from_padded_bgra(&[0; 64], 16).is_err());
+            #[cfg(feature = "image-input")]
+            {
+                let image = image::RgbImage::new(4, 4);
+                assert!(frame.fill_from_image_rgb(&image).is_err());
+                assert!(frame.fill_from_image_rgb_cropped(&image, 0, 0).is_err());
+            }
+        }
+    }
+
+    #[cfg(feature = "image-input")]
+    #[test]
+    fn fill_from_image_rgb_copies_pixels() {
+        let image = image::RgbImage::from_fn(3, 2, |x, y| image::Rgb([x as u8, y as u8, 7]));
+        let mut frame = Frame::new(PixelFormat::Rgb24, 3, 2).unwrap();
+        frame.fill_from_image_rgb(&image).unwrap();
+        assert_eq!(row(&frame, 0, 1, 9), [0, 1, 7, 1, 1, 7, 2, 1, 7]);
+    }
+}
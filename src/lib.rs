@@ -1,26 +1,45 @@
 //! Provides a simple and easy-to-use video encoder, which allows turning a series
 //! of images into a video using a simple interface with sane defaults.
 //!
-//! Videos produced will be compressed using H.264, and can be written to any
+//! Videos produced will be compressed using H.264 by default, and can be written to any
 //! container format supported by ffmpeg.
 
 #![deny(missing_docs, unconditional_panic)]
 
 use std::{
     error::Error,
-    ffi::CStr,
+    fmt, fs,
+    ops::{BitOr, BitOrAssign, RangeInclusive},
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use ffmpeg_sys_next::{
-    av_log_set_level, av_make_error_string, AVCodecID, AVPixelFormat, AV_ERROR_MAX_STRING_SIZE,
-    AV_LOG_QUIET,
+    AVChromaLocation, AVFieldOrder, AVPixelFormat, AVFMT_FLAG_BITEXACT, AVFMT_FLAG_FLUSH_PACKETS,
+    AV_DISPOSITION_COMMENT, AV_DISPOSITION_DEFAULT, AV_DISPOSITION_DUB, AV_DISPOSITION_FORCED,
+    AV_DISPOSITION_ORIGINAL,
 };
 
-use crate::output::OutputStream;
+use crate::output::{FrameExtras, OutputStream};
 
+mod async_encoder;
+mod capabilities;
+mod effective_settings;
+mod error;
+mod estimate;
 mod frame;
+mod logging;
 mod output;
+mod packet_encoder;
+#[cfg(feature = "serde")]
+mod summary;
+mod tee;
+mod timing;
+mod variable_rate;
+
+#[cfg(feature = "bytemuck")]
+pub use bytemuck;
 
 #[cfg(feature = "cairo-input")]
 pub use cairo;
@@ -28,9 +47,37 @@ pub use cairo;
 #[cfg(feature = "image-input")]
 pub use image;
 
-pub use crate::frame::Frame;
+#[cfg(feature = "tempfile")]
+pub use tempfile;
+
+#[cfg(feature = "tiny-skia-input")]
+pub use tiny_skia;
+
+pub use crate::{
+    async_encoder::{AsyncEncoder, FinishHandle},
+    capabilities::{CodecOption, CodecOptionType, EncoderCapabilities},
+    effective_settings::EffectiveSettings,
+    error::EncoderError,
+    estimate::estimate_output_size,
+    frame::Frame,
+    logging::{set_log_level, LogLevel},
+    packet_encoder::{EncodedPacket, PacketEncoder, PacketStats},
+    tee::{TeeEncoder, TeeOutput},
+    timing::TimingStats,
+    variable_rate::VariableRateEncoder,
+};
+
+#[cfg(feature = "compact-errors")]
+pub use crate::error::AvError;
+#[cfg(feature = "serde")]
+pub use crate::summary::EncodeSummary;
+
+#[cfg(not(feature = "compact-errors"))]
+fn make_av_error(action: &'static str, err: i32) -> Box<dyn Error> {
+    use std::ffi::CStr;
+
+    use ffmpeg_sys_next::{av_make_error_string, AV_ERROR_MAX_STRING_SIZE};
 
-fn make_av_error(action: impl Into<String>, err: i32) -> Box<dyn Error> {
     let mut buffer = [0u8; AV_ERROR_MAX_STRING_SIZE];
     unsafe {
         av_make_error_string(buffer.as_mut_ptr() as *mut i8, buffer.len(), err);
@@ -42,19 +89,25 @@ fn make_av_error(action: impl Into<String>, err: i32) -> Box<dyn Error> {
         .find(|(_, x)| **x == 0)
         .expect("av_make_error_string returned string without null terminator");
 
-    let str = CStr::from_bytes_with_nul(&buffer[..idx]).unwrap();
+    let str = CStr::from_bytes_with_nul(&buffer[..=idx]).unwrap();
     format!(
         "Error {}: {}",
-        action.into(),
+        action,
         str.to_str()
             .expect("av_make_error_string returned invalid UTF-8")
     )
     .into()
 }
 
+#[cfg(feature = "compact-errors")]
+fn make_av_error(action: &'static str, err: i32) -> Box<dyn Error> {
+    Box::new(AvError { action, code: err })
+}
+
 /// The possible presets for libx264. These are listed in descending order of speed.
 /// See <https://trac.ffmpeg.org/wiki/Encode/H.264> for more information.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
 pub enum X264Preset {
     /// The fastest preset
     UltraFast,
@@ -72,8 +125,11 @@ pub enum X264Preset {
     Slow,
     #[allow(missing_docs)]
     Slower,
-    /// The slowest preset
+    /// The slowest preset that's generally worth using
     VerySlow,
+    /// The slowest preset, which is much slower than [`Self::VerySlow`] for very little
+    /// improvement. Mostly useful for archival or testing.
+    Placebo,
 }
 impl X264Preset {
     fn as_bytes_with_nul(&self) -> *const i8 {
@@ -87,49 +143,681 @@ impl X264Preset {
             X264Preset::Slow => "slow\0",
             X264Preset::Slower => "slower\0",
             X264Preset::VerySlow => "veryslow\0",
+            X264Preset::Placebo => "placebo\0",
         }
         .as_ptr() as *const i8
     }
 }
 
-#[derive(Default)]
+/// The motion estimation methods of libx264, which searches previous frames for the parts
+/// of each frame that moved. These are listed in ascending order of thoroughness, and so
+/// descending order of speed. See [`SimpleVideoEncoderBuilder::motion_estimation`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum MeMethod {
+    /// Diamond search, the fastest method.
+    Dia,
+    /// Hexagonal search. This is the default for most presets.
+    Hex,
+    /// Uneven multi-hexagon search.
+    Umh,
+    /// Exhaustive search of the whole motion search range.
+    Esa,
+    /// Transformed exhaustive search, the slowest method.
+    Tesa,
+}
+impl MeMethod {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MeMethod::Dia => "dia",
+            MeMethod::Hex => "hex",
+            MeMethod::Umh => "umh",
+            MeMethod::Esa => "esa",
+            MeMethod::Tesa => "tesa",
+        }
+    }
+}
+
+/// A callback run on each frame just before it is encoded. See [`SimpleVideoEncoderBuilder::pre_encode`].
+type PreEncodeHook = Arc<Mutex<dyn FnMut(&mut Frame, u64) + Send>>;
+
+/// A callback run for each packet the encoder produces. See [`SimpleVideoEncoderBuilder::on_packet`].
+type PacketCallback = Arc<Mutex<dyn FnMut(&PacketStats) + Send>>;
+
+/// The user-provided callbacks, which are handed from the builder to the encoder. They're
+/// shared rather than boxed so that builders can be cloned; every encoder built from a clone
+/// calls the same function.
+#[derive(Default, Clone)]
+struct Callbacks {
+    pre_encode: Option<PreEncodeHook>,
+    on_packet: Option<PacketCallback>,
+}
+
+/// Video codecs which ffmpeg may be able to encode, depending on how it was built.
+/// Use [`EncoderCapabilities::detect`] to find out which are available.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VideoCodec {
+    /// H.264, encoded with libx264
+    H264,
+    /// H.265 (HEVC), encoded with libx265
+    H265,
+    /// VP9, encoded with libvpx
+    Vp9,
+    /// AV1, encoded with SVT-AV1
+    Av1,
+    /// Animated GIF, using ffmpeg's built-in encoder. Frames are reduced to a fixed palette
+    /// of 256 colors; see [`SimpleVideoEncoderBuilder::gif_dither`]. Most other encoding
+    /// settings don't apply.
+    Gif,
+}
+impl VideoCodec {
+    pub(crate) const ALL: [VideoCodec; 5] = [
+        VideoCodec::H264,
+        VideoCodec::H265,
+        VideoCodec::Vp9,
+        VideoCodec::Av1,
+        VideoCodec::Gif,
+    ];
+
+    /// The name of the ffmpeg encoder used for this codec, with a nul terminator.
+    pub(crate) fn encoder_name(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "libx264\0",
+            VideoCodec::H265 => "libx265\0",
+            VideoCodec::Vp9 => "libvpx-vp9\0",
+            VideoCodec::Av1 => "libsvtav1\0",
+            VideoCodec::Gif => "gif\0",
+        }
+    }
+
+    /// Lists the options specific to this codec's encoder in the ffmpeg libraries this
+    /// program is running with, such as libx264's `tune`, for showing to users or for
+    /// building a settings UI. Empty if the encoder isn't available.
+    pub fn options(&self) -> Vec<CodecOption> {
+        capabilities::codec_options(self.encoder_name())
+    }
+
+    /// The valid CRF values for this codec's encoder, or `None` if it has no CRF mode.
+    pub(crate) fn crf_range(&self) -> Option<RangeInclusive<i64>> {
+        match self {
+            VideoCodec::H264 | VideoCodec::H265 => Some(0..=51),
+            VideoCodec::Vp9 | VideoCodec::Av1 => Some(0..=63),
+            VideoCodec::Gif => None,
+        }
+    }
+}
+
+/// How colors are approximated when reducing frames to the fixed palette of a GIF.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum DitherMode {
+    /// Each pixel is replaced by the closest palette color. This causes visible banding in
+    /// gradients, but compresses best.
+    None,
+    /// An ordered dither, which adds a regular pattern. This is a good compromise between
+    /// quality and file size.
+    Bayer,
+    /// Error diffusion, which gives the smoothest gradients but adds noise that changes
+    /// between frames and compresses poorly.
+    ErrorDiffusion,
+}
+impl DitherMode {
+    /// The value of swscale's `sws_dither` option for this mode, with a nul terminator.
+    fn as_sws_option(&self) -> &'static str {
+        match self {
+            DitherMode::None => "none\0",
+            DitherMode::Bayer => "bayer\0",
+            DitherMode::ErrorDiffusion => "ed\0",
+        }
+    }
+}
+
+/// The layout of the pixels in a [`Frame`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PixelFormat {
+    /// Packed 8-bit RGB, 3 bytes per pixel. This is the format of frames returned by
+    /// [`SimpleVideoEncoder::new_frame`].
+    Rgb24,
+    /// Packed 8-bit RGBA, 4 bytes per pixel.
+    Rgba,
+    /// Packed 8-bit BGRA, 4 bytes per pixel.
+    Bgra,
+    /// Planar 8-bit YUV with chroma halved horizontally and vertically.
+    Yuv420p,
+    /// Planar 8-bit YUV with no chroma subsampling.
+    Yuv444p,
+    /// 8-bit YUV with a luma plane followed by a plane of interleaved U and V samples,
+    /// with chroma halved horizontally and vertically.
+    Nv12,
+    /// 8-bit grayscale.
+    Gray8,
+    /// Packed 16-bit RGB, 6 bytes per pixel, with each sample stored little-endian.
+    Rgb48,
+    /// Planar 10-bit YUV with chroma halved horizontally and vertically. Each sample takes
+    /// 2 bytes, stored little-endian in the low 10 bits.
+    Yuv420p10le,
+}
+impl PixelFormat {
+    fn as_av(&self) -> AVPixelFormat {
+        match self {
+            PixelFormat::Rgb24 => AVPixelFormat::AV_PIX_FMT_RGB24,
+            PixelFormat::Rgba => AVPixelFormat::AV_PIX_FMT_RGBA,
+            PixelFormat::Bgra => AVPixelFormat::AV_PIX_FMT_BGRA,
+            PixelFormat::Yuv420p => AVPixelFormat::AV_PIX_FMT_YUV420P,
+            PixelFormat::Yuv444p => AVPixelFormat::AV_PIX_FMT_YUV444P,
+            PixelFormat::Nv12 => AVPixelFormat::AV_PIX_FMT_NV12,
+            PixelFormat::Gray8 => AVPixelFormat::AV_PIX_FMT_GRAY8,
+            PixelFormat::Rgb48 => AVPixelFormat::AV_PIX_FMT_RGB48LE,
+            PixelFormat::Yuv420p10le => AVPixelFormat::AV_PIX_FMT_YUV420P10LE,
+        }
+    }
+}
+
+/// A description of raw frame data given to [`SimpleVideoEncoder::append_raw`], such as a
+/// frame dumped to a file by another tool.
+///
+/// The planes of the pixel format are stored one after another, with no gap between them.
+/// `stride` is the number of bytes from the start of one row of the first plane to the
+/// start of the next, which may include padding. Planes with shorter rows, like the chroma
+/// planes of [`PixelFormat::Yuv420p`], have their stride scaled down in proportion, so
+/// tightly packed data has a tightly packed stride for every plane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawFrameFormat {
+    /// The layout of the pixels.
+    pub pixel_format: PixelFormat,
+    /// The width of the frame in pixels.
+    pub width: i32,
+    /// The height of the frame in pixels.
+    pub height: i32,
+    /// The number of bytes between the starts of consecutive rows of the first plane.
+    pub stride: usize,
+}
+
+/// How the pixels given to [`Frame::fill_from_pod`] are laid out in memory. Pixels are
+/// always tightly packed, with no padding between rows.
+///
+/// *Only enabled with the `bytemuck` feature.*
+#[cfg(feature = "bytemuck")]
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum PixelLayout {
+    /// 8-bit RGB, 3 bytes per pixel, such as `[u8; 3]`.
+    Rgb8,
+    /// 8-bit RGBA, 4 bytes per pixel, such as `[u8; 4]`, or a `u32` of the form `0xAABBGGRR`
+    /// on little-endian platforms.
+    Rgba8,
+    /// 8-bit BGRA, 4 bytes per pixel, such as a `u32` of the form `0xAARRGGBB` on
+    /// little-endian platforms.
+    Bgra8,
+    /// 32-bit floating-point RGBA, 16 bytes per pixel, such as `[f32; 4]`. `0.0` to `1.0`
+    /// covers the full range of the output, and values outside it are clamped.
+    RgbaF32,
+}
+#[cfg(feature = "bytemuck")]
+impl PixelLayout {
+    fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelLayout::Rgb8 => 3,
+            PixelLayout::Rgba8 | PixelLayout::Bgra8 => 4,
+            PixelLayout::RgbaF32 => 16,
+        }
+    }
+
+    /// Reads one pixel as RGBA, scaled up to 16 bits per channel.
+    fn read_rgba16(&self, pixel: &[u8]) -> [u16; 4] {
+        // Multiplying by 257 maps 255 to 65535 exactly, so 8-bit values survive a round trip.
+        let widen = |value: u8| value as u16 * 257;
+        match self {
+            PixelLayout::Rgb8 => [widen(pixel[0]), widen(pixel[1]), widen(pixel[2]), u16::MAX],
+            PixelLayout::Rgba8 => [
+                widen(pixel[0]),
+                widen(pixel[1]),
+                widen(pixel[2]),
+                widen(pixel[3]),
+            ],
+            PixelLayout::Bgra8 => [
+                widen(pixel[2]),
+                widen(pixel[1]),
+                widen(pixel[0]),
+                widen(pixel[3]),
+            ],
+            PixelLayout::RgbaF32 => {
+                let mut channels = [0; 4];
+                for (channel, bytes) in channels.iter_mut().zip(pixel.chunks_exact(4)) {
+                    let value = f32::from_ne_bytes(bytes.try_into().unwrap());
+                    // NaN clamps to 0 through the saturating cast.
+                    *channel = (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16;
+                }
+                channels
+            }
+        }
+    }
+}
+
+/// How much the chroma (color) planes of the output are downsampled relative to the luma
+/// (brightness) plane. Less subsampling preserves more color detail, at the cost of larger
+/// files and reduced compatibility with hardware decoders.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum ChromaSubsampling {
+    /// Chroma is halved both horizontally and vertically. This is supported by virtually
+    /// every player.
+    Yuv420,
+    /// Chroma is halved horizontally only.
+    Yuv422,
+    /// No chroma subsampling.
+    Yuv444,
+}
+impl ChromaSubsampling {
+    fn as_av(&self, bit_depth: BitDepth) -> AVPixelFormat {
+        match (self, bit_depth) {
+            (ChromaSubsampling::Yuv420, BitDepth::Eight) => AVPixelFormat::AV_PIX_FMT_YUV420P,
+            (ChromaSubsampling::Yuv422, BitDepth::Eight) => AVPixelFormat::AV_PIX_FMT_YUV422P,
+            (ChromaSubsampling::Yuv444, BitDepth::Eight) => AVPixelFormat::AV_PIX_FMT_YUV444P,
+            (ChromaSubsampling::Yuv420, BitDepth::Ten) => AVPixelFormat::AV_PIX_FMT_YUV420P10LE,
+            (ChromaSubsampling::Yuv422, BitDepth::Ten) => AVPixelFormat::AV_PIX_FMT_YUV422P10LE,
+            (ChromaSubsampling::Yuv444, BitDepth::Ten) => AVPixelFormat::AV_PIX_FMT_YUV444P10LE,
+        }
+    }
+
+    /// The name of the H.264 profile needed for 10-bit output with this subsampling.
+    fn h264_high_bit_depth_profile(&self) -> &'static [u8] {
+        match self {
+            ChromaSubsampling::Yuv420 => b"high10\0",
+            ChromaSubsampling::Yuv422 => b"high422\0",
+            ChromaSubsampling::Yuv444 => b"high444\0",
+        }
+    }
+}
+
+/// The number of bits used for each sample of the output. Higher bit depths reduce banding
+/// in smooth gradients, but aren't supported by as many players.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BitDepth {
+    /// 8 bits per sample, which every player supports.
+    Eight,
+    /// 10 bits per sample. This requires an encoder built with 10-bit support.
+    Ten,
+}
+
+/// The position of chroma samples relative to luma samples in subsampled output.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum ChromaLocation {
+    /// Chroma samples are horizontally aligned with the left luma sample and vertically
+    /// centered. This is what MPEG-2, H.264, and H.265 use by default.
+    Left,
+    /// Chroma samples are centered between luma samples, as in MPEG-1 and JPEG.
+    Center,
+    #[allow(missing_docs)]
+    TopLeft,
+    #[allow(missing_docs)]
+    Top,
+    #[allow(missing_docs)]
+    BottomLeft,
+    #[allow(missing_docs)]
+    Bottom,
+}
+impl ChromaLocation {
+    fn as_av(&self) -> AVChromaLocation {
+        match self {
+            ChromaLocation::Left => AVChromaLocation::AVCHROMA_LOC_LEFT,
+            ChromaLocation::Center => AVChromaLocation::AVCHROMA_LOC_CENTER,
+            ChromaLocation::TopLeft => AVChromaLocation::AVCHROMA_LOC_TOPLEFT,
+            ChromaLocation::Top => AVChromaLocation::AVCHROMA_LOC_TOP,
+            ChromaLocation::BottomLeft => AVChromaLocation::AVCHROMA_LOC_BOTTOMLEFT,
+            ChromaLocation::Bottom => AVChromaLocation::AVCHROMA_LOC_BOTTOM,
+        }
+    }
+}
+
+/// Implements the operations shared by the flag types below, which wrap an ffmpeg bitmask.
+macro_rules! flags_ops {
+    ($name:ident) => {
+        impl $name {
+            /// No flags.
+            pub const NONE: $name = $name(0);
+
+            /// Returns true if every flag in `other` is also set in `self`.
+            pub fn contains(self, other: $name) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            fn as_av(self) -> i32 {
+                self.0
+            }
+        }
+        impl BitOr for $name {
+            type Output = $name;
+
+            fn bitor(self, rhs: $name) -> $name {
+                $name(self.0 | rhs.0)
+            }
+        }
+        impl BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: $name) {
+                self.0 |= rhs.0;
+            }
+        }
+    };
+}
+
+/// Format-level flags for the muxer, given to [`SimpleVideoEncoderBuilder::muxer_flags`].
+/// Flags can be combined with `|`.
+///
+/// ffmpeg has more format flags than these, but the others either only apply when reading
+/// files, like generating missing timestamps, or are already set as needed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MuxerFlags(i32);
+impl MuxerFlags {
+    /// Pass every packet on to the output as soon as it's written, the same as
+    /// [`SimpleVideoEncoderBuilder::flush_packets`]. Safe with any output, but only worth
+    /// the extra system calls for pipes and network streams.
+    pub const FLUSH_PACKETS: MuxerFlags = MuxerFlags(AVFMT_FLAG_FLUSH_PACKETS as i32);
+    /// Leave out anything that would differ between runs, such as the ffmpeg version and
+    /// creation time, so the same frames and settings always give identical files. Safe
+    /// with any output; this is mostly useful for tests.
+    pub const BIT_EXACT: MuxerFlags = MuxerFlags(AVFMT_FLAG_BITEXACT as i32);
+}
+flags_ops!(MuxerFlags);
+
+/// Flags describing the video stream to players, given to
+/// [`SimpleVideoEncoderBuilder::video_disposition`]. Flags can be combined with `|`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamDisposition(i32);
+impl StreamDisposition {
+    /// The stream players should pick when there's more than one to choose from.
+    pub const DEFAULT: StreamDisposition = StreamDisposition(AV_DISPOSITION_DEFAULT as i32);
+    /// The stream should be shown even if the player would otherwise not show it.
+    pub const FORCED: StreamDisposition = StreamDisposition(AV_DISPOSITION_FORCED as i32);
+    /// The stream is the original version, rather than an edited or dubbed one.
+    pub const ORIGINAL: StreamDisposition = StreamDisposition(AV_DISPOSITION_ORIGINAL as i32);
+    /// The stream is a dubbed version.
+    pub const DUB: StreamDisposition = StreamDisposition(AV_DISPOSITION_DUB as i32);
+    /// The stream contains commentary.
+    pub const COMMENT: StreamDisposition = StreamDisposition(AV_DISPOSITION_COMMENT as i32);
+}
+flags_ops!(StreamDisposition);
+
+/// How the two videos are arranged by [`SimpleVideoEncoder::run_comparison`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum ComparisonLayout {
+    /// The first video on the left half, and the second on the right half.
+    LeftRight,
+    /// The first video on the top half, and the second on the bottom half.
+    TopBottom,
+}
+
+/// Which field of an interlaced frame is displayed first.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum FieldOrder {
+    /// The field made of the even-numbered lines (counting from 0) is displayed first.
+    TopFieldFirst,
+    /// The field made of the odd-numbered lines is displayed first.
+    BottomFieldFirst,
+}
+impl FieldOrder {
+    fn as_av(&self) -> AVFieldOrder {
+        match self {
+            FieldOrder::TopFieldFirst => AVFieldOrder::AV_FIELD_TT,
+            FieldOrder::BottomFieldFirst => AVFieldOrder::AV_FIELD_BB,
+        }
+    }
+}
+
+/// The ways the encoder can decide how many bits to spend on each frame.
+/// See [`SimpleVideoEncoderBuilder::rate_control`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum RateControl {
+    /// Constant rate factor, which targets a constant perceptual quality. Equivalent to
+    /// [`SimpleVideoEncoderBuilder::crf`].
+    Crf(i64),
+    /// Targets an average bitrate in bits/second. Equivalent to
+    /// [`SimpleVideoEncoderBuilder::bitrate`].
+    Bitrate(i64),
+    /// Encodes every frame with the same quantizer, in the range 0-51 for H.264. Unlike CRF,
+    /// this doesn't adapt to the content at all, so it mostly makes sense for analysis rather
+    /// than for producing videos to watch. Supported by the H.264, H.265, and AV1 encoders.
+    ConstantQp(u8),
+}
+
+/// Parameters for AV1 film grain synthesis. The encoder estimates the grain present in the
+/// input, removes it before encoding, and signals it in the output so that the decoder can
+/// add similar-looking grain back. This saves a large number of bits for grainy content,
+/// such as scanned film.
+#[derive(Clone, Copy, Debug)]
+pub struct FilmGrainParams {
+    strength: u8,
+    denoise: bool,
+}
+impl FilmGrainParams {
+    /// Creates film grain parameters with the given strength, in the range 1-50.
+    /// Higher values synthesize stronger grain. Denoising is enabled by default.
+    pub fn new(strength: u8) -> Self {
+        Self {
+            strength,
+            denoise: true,
+        }
+    }
+
+    /// Sets whether the encoder should remove grain from the input before encoding it.
+    /// Disabling this keeps the original grain in the encoded pictures, and the synthesized
+    /// grain is added on top.
+    pub fn denoise(mut self, denoise: bool) -> Self {
+        self.denoise = denoise;
+        self
+    }
+}
+
+/// The color volume of the display an HDR video was mastered on, which players use to map
+/// the video to the capabilities of their own display. See
+/// [`SimpleVideoEncoderBuilder::hdr10`].
+///
+/// Chromaticities are CIE 1931 xy coordinates, and luminances are in cd/m² (nits).
+#[derive(Clone, Copy, Debug)]
+pub struct MasteringDisplay {
+    /// The chromaticity of the red primary.
+    pub red: (f64, f64),
+    /// The chromaticity of the green primary.
+    pub green: (f64, f64),
+    /// The chromaticity of the blue primary.
+    pub blue: (f64, f64),
+    /// The chromaticity of the white point.
+    pub white_point: (f64, f64),
+    /// The lowest luminance the display can show.
+    pub min_luminance: f64,
+    /// The highest luminance the display can show.
+    pub max_luminance: f64,
+}
+impl MasteringDisplay {
+    /// A display with DCI-P3 primaries, a D65 white point, and a peak luminance of 1000 nits,
+    /// which is the most common mastering setup for HDR10 content.
+    pub const P3_D65_1000_NITS: Self = Self {
+        red: (0.680, 0.320),
+        green: (0.265, 0.690),
+        blue: (0.150, 0.060),
+        white_point: (0.3127, 0.3290),
+        min_luminance: 0.0001,
+        max_luminance: 1000.0,
+    };
+}
+
+/// The brightness of the content of an HDR video, in cd/m² (nits). See
+/// [`SimpleVideoEncoderBuilder::hdr10`].
+#[derive(Clone, Copy, Debug)]
+pub struct ContentLightLevel {
+    /// The maximum content light level: the brightness of the brightest pixel in the video.
+    pub max_cll: u32,
+    /// The maximum frame-average light level: the average brightness of the brightest frame.
+    pub max_fall: u32,
+}
+
+#[derive(Default, Debug, Clone)]
 struct OptionalSettings {
+    format_name: Option<String>,
+    codec: Option<VideoCodec>,
     crf: Option<i64>,
+    qp: Option<u8>,
     bitrate: Option<i64>,
     gop_size: Option<i32>,
+    keyframe_every: Option<Duration>,
+    keyframe_interval: Option<f64>,
+    fixed_gop: bool,
+    closed_gop: Option<bool>,
+    ref_frames: Option<i32>,
+    motion_estimation: Option<MeMethod>,
+    me_range: Option<i32>,
+    trellis: Option<u8>,
+    disable_psy: bool,
+    max_qdiff: Option<i32>,
+    min_gop_size: Option<i32>,
+    b_quant_factor: Option<f32>,
+    initial_buffer_occupancy: Option<i64>,
     preset: Option<X264Preset>,
+    chroma_subsampling: Option<ChromaSubsampling>,
+    bit_depth: Option<BitDepth>,
+    alpha: bool,
+    chroma_location: Option<ChromaLocation>,
+    // top, bottom, left, and right
+    display_crop: Option<(u32, u32, u32, u32)>,
+    field_order: Option<FieldOrder>,
+    film_grain: Option<FilmGrainParams>,
+    hdr10: Option<(MasteringDisplay, ContentLightLevel)>,
+    gif_dither: Option<DitherMode>,
+    sws_sharpen: Option<f32>,
+    denoise: Option<f32>,
+    muxer_options: Vec<(String, String)>,
+    mp4_brand: Option<String>,
+    // start and end times in seconds, and title
+    chapters: Vec<(f64, f64, String)>,
+    #[cfg(feature = "image-input")]
+    cover_image: Option<image::RgbImage>,
+    #[cfg(feature = "serde")]
+    sidecar_path: Option<PathBuf>,
+    max_interleave_delta: Option<i64>,
+    flush_packets: Option<bool>,
+    muxer_flags: MuxerFlags,
+    video_disposition: Option<StreamDisposition>,
+    realtime_pacing: bool,
+    skip_duplicate_frames: bool,
+    skip_bad_frames: bool,
+    use_frame_pts: bool,
+    start_pts: Option<i64>,
+    collect_timing: bool,
+    deterministic: bool,
+    create_dirs: bool,
+    streaming: bool,
+    #[cfg(feature = "cairo-input")]
+    burn_frame_number: bool,
+}
+impl OptionalSettings {
+    /// Makes sure the directory `filename` will be written to exists, creating it if enabled.
+    fn prepare_output_dir(&self, filename: &Path) -> Result<(), Box<dyn Error>> {
+        // Network URLs aren't paths on disk, so they have no directory to check.
+        if self.streaming {
+            return Ok(());
+        }
+
+        if let Some(dir) = filename.parent() {
+            // A bare filename has an empty parent, which means the current directory.
+            if !dir.as_os_str().is_empty() && !dir.is_dir() {
+                if self.create_dirs {
+                    fs::create_dir_all(dir)?;
+                } else {
+                    return Err(EncoderError::DirectoryMissing(dir.to_path_buf()).into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The number of frames between keyframes, from whichever of the GOP settings was used.
+    fn gop_frames(&self, framerate: (i32, i32)) -> i32 {
+        let gop_seconds = self
+            .keyframe_interval
+            .or(self.keyframe_every.map(|every| every.as_secs_f64()));
+        match gop_seconds {
+            Some(seconds) => {
+                ((seconds * framerate.0 as f64 / framerate.1 as f64).round() as i32).max(1)
+            }
+            None => self.gop_size.unwrap_or(10),
+        }
+    }
 }
 
 /// Helper to build a SimpleVideoEncoder, allowing you to specify additional options.
+///
+/// Builders can be cloned to create several encoders which differ in only a few settings.
+#[derive(Clone)]
 pub struct SimpleVideoEncoderBuilder {
     filename: PathBuf,
     width: i32,
     height: i32,
-    framerate: i32,
+    // as a fraction, (numerator, denominator)
+    framerate: (i32, i32),
 
     settings: OptionalSettings,
+    callbacks: Callbacks,
+}
+impl fmt::Debug for SimpleVideoEncoderBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The hook is a closure, so all that can be shown is whether there is one.
+        f.debug_struct("SimpleVideoEncoderBuilder")
+            .field("filename", &self.filename)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("framerate", &self.framerate)
+            .field("settings", &self.settings)
+            .field("pre_encode", &self.callbacks.pre_encode.is_some())
+            .field("on_packet", &self.callbacks.on_packet.is_some())
+            .finish()
+    }
 }
 impl SimpleVideoEncoderBuilder {
     fn new<P: AsRef<Path>>(filename: P, width: i32, height: i32, framerate: i32) -> Self {
-        // Disable libav logging to avoid spamming stderr unexpectedly
-        unsafe {
-            av_log_set_level(AV_LOG_QUIET);
-        }
+        logging::quiet_by_default();
 
         Self {
             filename: filename.as_ref().to_path_buf(),
             width,
             height,
-            framerate,
+            framerate: (framerate, 1),
 
             settings: Default::default(),
+            callbacks: Default::default(),
         }
     }
 
+    /// Sets the container format by name, such as `"mp4"` or `"matroska"`, instead of
+    /// detecting it from the file extension. Run `ffmpeg -muxers` to list the available
+    /// formats.
+    pub fn container_format(mut self, format_name: impl Into<String>) -> Self {
+        self.settings.format_name = Some(format_name.into());
+        self
+    }
+
+    /// Sets the codec used to compress the video. Check [`EncoderCapabilities`] to find out which
+    /// codecs are available. Make sure the container format supports the codec; for example,
+    /// VP9 and AV1 are commonly stored in .webm or .mkv files.
+    ///
+    /// Defaults to H.264.
+    pub fn codec(mut self, codec: VideoCodec) -> Self {
+        self.settings.codec = Some(codec);
+        self
+    }
+
     /// Sets the CRF, the constant-rate function. See <https://trac.ffmpeg.org/wiki/Encode/H.264> for more details.
-    /// The range of values is 0-51; lower values produce higher-quality output.
+    /// The range of values is 0-51 (0-63 for VP9 and AV1); lower values produce higher-quality output.
     /// Values around 17-18 should be visually lossless. 22-23 are reasonable starting points.
-    /// If you specify this, the bitrate setting is ignored.
+    /// If you specify this, the bitrate setting is ignored. Out of range values cause
+    /// [`Self::build`] to fail with [`EncoderError::InvalidCrf`].
     ///
     /// Unspecified by default.
     pub fn crf(mut self, crf: i64) -> Self {
@@ -137,10 +825,140 @@ impl SimpleVideoEncoderBuilder {
         self
     }
 
+    /// Enables AV1 film grain synthesis; see [`FilmGrainParams`] for details.
+    /// This is only supported with [`VideoCodec::Av1`].
+    ///
+    /// Disabled by default.
+    pub fn film_grain(mut self, params: FilmGrainParams) -> Self {
+        self.settings.film_grain = Some(params);
+        self
+    }
+
+    /// Marks the video as HDR10: BT.2020 primaries with the PQ (SMPTE 2084) transfer
+    /// function, along with metadata describing the display it was mastered on and how
+    /// bright its content is. Frames must already contain PQ-encoded BT.2020 colors; they
+    /// aren't converted, other than from RGB to YUV with the BT.2020 matrix.
+    ///
+    /// This requires 10-bit output (see [`Self::bit_depth`]), and is only supported with
+    /// [`VideoCodec::H265`] and [`VideoCodec::Av1`]. The AV1 encoders don't read the
+    /// mastering display and light level metadata, so with AV1 only the color tags are
+    /// written.
+    pub fn hdr10(mut self, mastering: MasteringDisplay, light_level: ContentLightLevel) -> Self {
+        self.settings.hdr10 = Some((mastering, light_level));
+        self
+    }
+
+    /// Sets how the encoder decides how many bits to spend on each frame. This replaces any
+    /// mode set before, including with [`Self::crf`] or [`Self::bitrate`].
+    ///
+    /// Defaults to a bitrate of 800 kbps.
+    pub fn rate_control(mut self, rate_control: RateControl) -> Self {
+        self.settings.crf = None;
+        self.settings.bitrate = None;
+        self.settings.qp = None;
+        match rate_control {
+            RateControl::Crf(crf) => self.settings.crf = Some(crf),
+            RateControl::Bitrate(bitrate) => self.settings.bitrate = Some(bitrate),
+            RateControl::ConstantQp(qp) => self.settings.qp = Some(qp),
+        }
+        self
+    }
+
+    /// Set the trellis quantization mode of x264: 0 disables it, 1 uses it only for the final
+    /// encode of each block, and 2 uses it for every decision. Higher modes improve quality
+    /// for the same bitrate, but are slower.
+    ///
+    /// This is only supported with [`VideoCodec::H264`]. By default, the preset chooses.
+    pub fn trellis(mut self, trellis: u8) -> Self {
+        self.settings.trellis = Some(trellis);
+        self
+    }
+
+    /// If enabled, turns off the encoder's psychovisual optimizations, which spend bits on
+    /// detail that looks good to people at the expense of objective metrics like PSNR and
+    /// SSIM. This is mostly useful for benchmarking; it makes the video look worse.
+    ///
+    /// This is only supported with [`VideoCodec::H264`] and [`VideoCodec::H265`]. Disabled
+    /// by default.
+    pub fn disable_psy(mut self, disable: bool) -> Self {
+        self.settings.disable_psy = disable;
+        self
+    }
+
+    /// Set the largest change in quantizer allowed between consecutive frames. Lower values
+    /// smooth out changes in quality, at the cost of following the target bitrate or quality
+    /// less closely. This has no effect with [`RateControl::ConstantQp`].
+    ///
+    /// By default, the encoder chooses.
+    pub fn max_qdiff(mut self, max_qdiff: i32) -> Self {
+        self.settings.max_qdiff = Some(max_qdiff);
+        self
+    }
+
+    /// Set how full, in bits, the decoder's rate control (VBV) buffer is assumed to be when
+    /// playback starts. A fuller buffer lets the first frames use more bits, but makes
+    /// players wait longer before they can start. This is mostly useful for tuning how
+    /// constant-bitrate streams start, and has no effect with [`RateControl::ConstantQp`].
+    ///
+    /// Only encoders which use a buffer take this into account. libvpx, used for
+    /// [`VideoCodec::Vp9`], always does; x264 and x265 only do when given a buffer size and
+    /// maximum rate, which this crate doesn't currently set, so they ignore it. By default,
+    /// the encoder chooses.
+    pub fn initial_buffer_occupancy(mut self, bits: i64) -> Self {
+        self.settings.initial_buffer_occupancy = Some(bits);
+        self
+    }
+
+    /// Set how much higher the quantizer of B-frames is than that of the P-frames around
+    /// them, as a factor. Higher values spend fewer bits on B-frames. This only matters when
+    /// the encoder uses B-frames.
+    ///
+    /// Used by the H.264 codec; the others ignore it. By default, the encoder chooses.
+    pub fn b_quant_factor(mut self, factor: f32) -> Self {
+        self.settings.b_quant_factor = Some(factor);
+        self
+    }
+
+    /// Set how colors are dithered when converting frames to the GIF palette.
+    ///
+    /// This is only supported with [`VideoCodec::Gif`]. By default, swscale chooses.
+    pub fn gif_dither(mut self, mode: DitherMode) -> Self {
+        self.settings.gif_dither = Some(mode);
+        self
+    }
+
+    /// Sharpen the brightness of frames while they're converted to the video's size and pixel
+    /// format, which helps keep detail when frames are downscaled. Around `1.0` is a mild
+    /// sharpening. Frames which already match the video aren't converted, so this has no
+    /// effect on them.
+    ///
+    /// No sharpening by default.
+    pub fn sws_sharpen(mut self, amount: f32) -> Self {
+        self.settings.sws_sharpen = Some(amount);
+        self
+    }
+
+    /// Blur frames slightly before they're encoded, which smooths out noise, such as sensor
+    /// noise in camera footage, that would otherwise take a lot of bits to encode.
+    /// `strength` is the standard deviation of a Gaussian blur, in pixels; around `0.5` to
+    /// `1.0` removes fine noise, and larger values visibly soften the picture.
+    ///
+    /// The blur is applied to a copy of the appended frame, after any [`Self::pre_encode`]
+    /// hook has run, so the frame itself is left unchanged. It works with any
+    /// [`PixelFormat`] with 8-bit samples; appending a frame in another format returns an
+    /// error.
+    ///
+    /// No denoising by default.
+    pub fn denoise(mut self, strength: f32) -> Self {
+        self.settings.denoise = Some(strength);
+        self
+    }
+
     /// Set the preset, a collection of options that allow trading off encoding speed for output file size and vice versa.
     /// If you combine this with setting the CRF, a slower preset will improve your bitrate.
     /// If you combine this with setting the bitrate, a slower preset will achieve better quality.
     /// See <https://trac.ffmpeg.org/wiki/Encode/H.264> for more information.
+    /// Only used with the H.264 and H.265 codecs.
     ///
     /// Defaults to Medium.
     pub fn preset(mut self, preset: X264Preset) -> Self {
@@ -161,81 +979,1495 @@ impl SimpleVideoEncoderBuilder {
     /// Set the group-of-pictures size, the maximum number of frames between I-frames (keyframes).
     /// Higher values will result in smaller file sizes, but most video players can only seek to I-frames,
     /// so setting this to a large value may hurt seekability. Consider this in combination with the
-    /// framerate.
+    /// framerate, or use [`Self::keyframe_every`] instead. See also [`Self::closed_gop`].
+    ///
+    /// A size of 1 makes every frame a keyframe (all-intra), which makes for much larger files
+    /// that are easy to edit and seek in. B-frames are disabled in that case.
     ///
     /// Defaults to 10.
     pub fn set_gop_size(mut self, gop_size: i32) -> Self {
         self.settings.gop_size = Some(gop_size);
+        self.settings.keyframe_every = None;
         self
     }
 
-    /// Produce a SimpleVideoEncoder using the specified settings.
-    pub fn build(self) -> Result<SimpleVideoEncoder, Box<dyn Error>> {
-        let mut format_context = OutputStream::new(
-            &self.filename,
-            AVCodecID::AV_CODEC_ID_H264,
-            self.width,
-            self.height,
-            self.framerate,
-            AVPixelFormat::AV_PIX_FMT_YUV420P,
-            &self.settings,
-        )?;
+    /// Set the minimum number of frames between keyframes. The encoder may still add a
+    /// keyframe at a scene change, but not within this many frames of the previous one, so
+    /// together with [`Self::set_gop_size`] this bounds the keyframe spacing on both sides.
+    /// Must be at least 1, and no larger than the GOP size. [`Self::fixed_gop`] overrides
+    /// this.
+    ///
+    /// This is only supported with [`VideoCodec::H264`]. By default, the encoder chooses.
+    pub fn min_gop_size(mut self, min_gop_size: i32) -> Self {
+        self.settings.min_gop_size = Some(min_gop_size);
+        self
+    }
 
-        format_context.open(&self.settings)?;
+    /// Set the group-of-pictures size as a duration rather than a number of frames. The
+    /// number of frames is calculated from the framerate, rounding to the nearest frame. This
+    /// replaces any earlier call to [`Self::set_gop_size`], and vice versa. It can't be
+    /// combined with [`Self::keyframe_interval_seconds`], which places keyframes by time
+    /// instead.
+    pub fn keyframe_every(mut self, interval: Duration) -> Self {
+        self.settings.keyframe_every = Some(interval);
+        self.settings.gop_size = None;
+        self
+    }
 
-        Ok(SimpleVideoEncoder {
-            width: self.width,
-            height: self.height,
-            format_context,
-        })
+    /// Overrides the framerate with a fractional one, `numerator / denominator` frames per
+    /// second. This is needed for the NTSC-derived rates, like 30000/1001 (29.97) or
+    /// 24000/1001 (23.976), which can't be represented exactly with a whole number.
+    pub fn rational_framerate(mut self, numerator: i32, denominator: i32) -> Self {
+        self.framerate = (numerator, denominator);
+        self
     }
-}
 
-/// A simple video encoder that can accept frames of video and will write them into a video file.
-pub struct SimpleVideoEncoder {
-    width: i32,
-    height: i32,
+    /// Places a keyframe every `seconds` seconds of video, measured from the frames'
+    /// timestamps rather than by counting frames. This keeps keyframes at exact time
+    /// intervals even when the framerate is fractional, which segmented formats like HLS
+    /// and DASH need. Overrides [`Self::set_gop_size`], and can't be combined with
+    /// [`Self::keyframe_every`].
+    pub fn keyframe_interval_seconds(mut self, seconds: f64) -> Self {
+        self.settings.keyframe_interval = Some(seconds);
+        self
+    }
 
-    format_context: OutputStream,
-}
-impl SimpleVideoEncoder {
-    /// Creates a SimpleVideoEncoder targeting the specified file name with default settings.
-    /// The container format will be detected automatically using the file extension.
-    pub fn new<P: AsRef<Path>>(
-        filename: P,
-        width: i32,
-        height: i32,
-        framerate: i32,
-    ) -> Result<Self, Box<dyn Error>> {
-        SimpleVideoEncoderBuilder::new(filename, width, height, framerate).build()
+    /// Choose between closed GOPs, where no frame references anything before the previous
+    /// keyframe, and open GOPs, where frames just before a keyframe may also reference
+    /// frames after it. Open GOPs compress slightly better, but only the first keyframe of
+    /// the video is guaranteed to be a clean seek point; with closed GOPs, playback can
+    /// start cleanly from any keyframe.
+    ///
+    /// Only affects the H.264 and H.265 codecs. By default, the encoder's own default is
+    /// used, which is closed GOPs for H.264 and open GOPs for H.265.
+    pub fn closed_gop(mut self, closed: bool) -> Self {
+        self.settings.closed_gop = Some(closed);
+        self
     }
 
-    /// Produces a builder targeting the specified file name, which allows specifying additional settings.
-    /// The container format will be detected automatically using the file extension.
-    pub fn builder<P: AsRef<Path>>(
-        filename: P,
-        width: i32,
-        height: i32,
-        framerate: i32,
-    ) -> SimpleVideoEncoderBuilder {
-        SimpleVideoEncoderBuilder::new(filename, width, height, framerate)
+    /// If enabled, keyframes are placed exactly every [`Self::set_gop_size`] frames and
+    /// nowhere else, instead of letting the encoder add extra keyframes at scene changes.
+    /// This is useful when the output will be split into segments, since every segment
+    /// needs to start on a keyframe.
+    ///
+    /// Disabled by default.
+    pub fn fixed_gop(mut self, enable: bool) -> Self {
+        self.settings.fixed_gop = enable;
+        self
     }
 
-    /// Finishes encoding the video and writes any trailer required by the container format.
-    /// (Note that mp4 has a required trailer.)
-    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
-        self.format_context.finish()
+    /// Set the maximum number of previous frames each frame may reference. Lowering this
+    /// can be needed for compatibility with decoders that have limited memory, such as on
+    /// some phones. Must be between 1 and 16.
+    ///
+    /// By default, the encoder chooses based on the preset.
+    pub fn ref_frames(mut self, ref_frames: i32) -> Self {
+        self.settings.ref_frames = Some(ref_frames);
+        self
     }
 
-    /// Adds the data in the frame as the video's next frame. This may mutate the frame.
-    /// After calling this, you may freely reuse the frame buffer.
-    pub fn append_frame(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error>> {
-        self.format_context.write_frame(frame)
+    /// Set the motion estimation method, trading encoding speed for compression. Slower
+    /// methods mostly help with content that has a lot of fast motion.
+    ///
+    /// This is only supported with [`VideoCodec::H264`]. By default, the preset chooses.
+    pub fn motion_estimation(mut self, method: MeMethod) -> Self {
+        self.settings.motion_estimation = Some(method);
+        self
     }
 
-    /// Creates a new frame buffer which can be filled with your data and then given to
-    /// [`Self::append_frame`].
-    pub fn new_frame(&self) -> Result<Frame, Box<dyn Error>> {
-        Frame::new(AVPixelFormat::AV_PIX_FMT_RGB24, self.width, self.height)
+    /// Set how far, in pixels, motion estimation searches from each block. Larger ranges
+    /// can find faster motion, but are slower. Must be at least 4.
+    ///
+    /// This is only supported with [`VideoCodec::H264`]. By default, the preset chooses.
+    pub fn me_range(mut self, range: i32) -> Self {
+        self.settings.me_range = Some(range);
+        self
+    }
+
+    /// Set the chroma subsampling of the output. Frames are converted to the matching pixel
+    /// format before being encoded.
+    ///
+    /// Not every codec supports every option; for example, the AV1 encoder only accepts
+    /// [`ChromaSubsampling::Yuv420`]. Unsupported combinations cause [`Self::build`] to fail.
+    ///
+    /// Defaults to [`ChromaSubsampling::Yuv420`].
+    pub fn chroma_subsampling(mut self, subsampling: ChromaSubsampling) -> Self {
+        self.settings.chroma_subsampling = Some(subsampling);
+        self
+    }
+
+    /// Set the bit depth of the output. Together with [`Self::chroma_subsampling`], this
+    /// decides the pixel format frames are converted to before being encoded. For H.264, 10-bit
+    /// output also selects the matching High profile, such as High 10 for 4:2:0.
+    ///
+    /// Not every encoder build supports 10-bit output, in which case [`Self::build`] fails.
+    /// GIFs are always 8-bit.
+    ///
+    /// Defaults to [`BitDepth::Eight`].
+    pub fn bit_depth(mut self, bit_depth: BitDepth) -> Self {
+        self.settings.bit_depth = Some(bit_depth);
+        self
+    }
+
+    /// If enabled, the output keeps an alpha channel, so transparent parts of frames stay
+    /// transparent. Frames from [`SimpleVideoEncoder::new_frame`] are then
+    /// [`PixelFormat::Rgba`] rather than RGB24, and can be filled with
+    /// [`Frame::fill_from_raw_planar`]; frames without alpha are encoded as fully opaque.
+    ///
+    /// This is only supported with [`VideoCodec::Vp9`] in 8-bit 4:2:0, and the alpha channel
+    /// is only kept by the WebM and Matroska containers. Building an encoder with any other
+    /// combination returns an error.
+    ///
+    /// Disabled by default.
+    pub fn with_alpha(mut self, enable: bool) -> Self {
+        self.settings.alpha = enable;
+        self
+    }
+
+    /// Set where chroma samples are located relative to luma samples. This is only signalled
+    /// in the output, and tells players how to upscale the chroma planes; the conversion to
+    /// the output pixel format is not affected.
+    ///
+    /// Unspecified by default, which leaves it up to the player to guess.
+    pub fn chroma_location(mut self, location: ChromaLocation) -> Self {
+        self.settings.chroma_location = Some(location);
+        self
+    }
+
+    /// Crop the given number of pixels from each edge when the video is displayed, while
+    /// still encoding the full frame. This is useful when the frames are padded to a
+    /// multiple of the encoder's block size, such as 1920x1088 content meant to be shown at
+    /// 1920x1080; without it, players show the padding.
+    ///
+    /// This is written into the H.264 bitstream, so it's only supported with
+    /// [`VideoCodec::H264`]. With 4:2:0 output every value must be even, and with 4:2:2
+    /// output `left` and `right` must be.
+    ///
+    /// No cropping by default.
+    pub fn display_crop(mut self, top: u32, bottom: u32, left: u32, right: u32) -> Self {
+        self.settings.display_crop = Some((top, bottom, left, right));
+        self
+    }
+
+    /// If enabled, frames which are identical to the previous frame aren't encoded. Instead,
+    /// the previous frame is shown for longer. This can greatly speed up encoding content
+    /// that is often static, such as screen recordings, at the cost of comparing each frame
+    /// to the previous one.
+    ///
+    /// This produces a video with a variable frame rate, which most containers support.
+    ///
+    /// Disabled by default.
+    pub fn skip_duplicate_frames(mut self, enable: bool) -> Self {
+        self.settings.skip_duplicate_frames = enable;
+        self
+    }
+
+    /// If enabled, a frame the encoder rejects is skipped instead of failing
+    /// [`SimpleVideoEncoder::append_frame`], and the previous frame is shown for longer.
+    /// Skipped frames are logged as warnings through ffmpeg's logging (see
+    /// [`set_log_level`]) and counted by [`SimpleVideoEncoder::dropped_frames`]. This suits
+    /// long unattended encodes, where losing an occasional frame is better than losing the
+    /// whole video.
+    ///
+    /// Errors which would affect every following frame, such as running out of memory or
+    /// failing to write the output, are still returned.
+    ///
+    /// Disabled by default.
+    pub fn skip_bad_frames(mut self, enable: bool) -> Self {
+        self.settings.skip_bad_frames = enable;
+        self
+    }
+
+    /// If enabled, each frame is shown at the timestamp set on it with [`Frame::set_pts`],
+    /// instead of directly after the previous frame. This gives full control over timing,
+    /// for example when frames are captured at irregular intervals.
+    ///
+    /// Every frame must have a timestamp, and each must be later than the last one; appending
+    /// a frame which doesn't will return an error.
+    ///
+    /// Disabled by default.
+    pub fn use_frame_pts(mut self, enable: bool) -> Self {
+        self.settings.use_frame_pts = enable;
+        self
+    }
+
+    /// Set the timestamp of the first frame, counted in frames, so that the video continues
+    /// on from a previous one, such as the previous segment of a longer recording. With
+    /// [`Self::use_frame_pts`], no frame may have an earlier timestamp than this.
+    ///
+    /// Keyframe intervals and the duration of the video are measured from this point, rather
+    /// than from zero.
+    ///
+    /// Defaults to 0.
+    pub fn start_pts(mut self, pts: i64) -> Self {
+        self.settings.start_pts = Some(pts);
+        self
+    }
+
+    /// If enabled, the time spent encoding each frame is recorded, and can be summarized with
+    /// [`SimpleVideoEncoder::timing_stats`]. This is meant for diagnosing performance problems.
+    ///
+    /// Disabled by default.
+    pub fn collect_timing(mut self, enable: bool) -> Self {
+        self.settings.collect_timing = enable;
+        self
+    }
+
+    /// If enabled, the same frames and settings always produce exactly the same output
+    /// bytes, which is useful for comparing output against a known hash in tests. This
+    /// encodes on a single thread, makes pixel format conversion bit-exact, and stops the
+    /// encoder and muxer from writing their version into the file. With
+    /// [`VideoCodec::H264`], x264's CPU-specific assembly is disabled too, so the output is
+    /// also the same on any machine; other encoders may still differ between CPUs.
+    ///
+    /// This makes encoding much slower, so it's only meant for tests. Output is only
+    /// reproducible with the same version of ffmpeg and its encoders.
+    ///
+    /// Disabled by default.
+    pub fn deterministic(mut self, enable: bool) -> Self {
+        self.settings.deterministic = enable;
+        self
+    }
+
+    /// Passes an option to the muxer, which writes the container format. For example,
+    /// `muxer_option("movflags", "+faststart")` moves the index of an mp4 file to the start so
+    /// that it can start playing before it has been completely downloaded.
+    /// See <https://ffmpeg.org/ffmpeg-formats.html#Muxers> for the options each muxer supports.
+    ///
+    /// This can be called multiple times to set several options. If the muxer does not
+    /// recognize one of the options, writing the header fails.
+    pub fn muxer_option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings.muxer_options.push((key.into(), value.into()));
+        self
+    }
+
+    /// Sets the major brand written in the header of an MP4/MOV file, such as `mp42`, which
+    /// some hardware players check before playing a file. Brands are always four characters.
+    /// The compatible brands are chosen by ffmpeg based on the file's contents, and can't be
+    /// set.
+    ///
+    /// Building an encoder for any other container with a brand set returns an error.
+    pub fn mp4_brand(mut self, major: &str) -> Self {
+        self.settings.mp4_brand = Some(major.to_owned());
+        self
+    }
+
+    /// Adds a chapter marker from `start_time` to `end_time`, both in seconds, which players
+    /// show in their seek bar or chapter menu. This can be called multiple times to add
+    /// several chapters.
+    ///
+    /// Only Matroska and MP4/MOV files support chapters; building an encoder for any other
+    /// container with chapters returns an error.
+    pub fn add_chapter(mut self, start_time: f64, end_time: f64, title: &str) -> Self {
+        self.settings
+            .chapters
+            .push((start_time, end_time, title.to_owned()));
+        self
+    }
+
+    /// Embeds `image` in the file as its cover, which file browsers and players show as a
+    /// thumbnail. It's stored as a PNG.
+    ///
+    /// Only MP4/MOV files support cover images; building an encoder for any other container
+    /// with a cover image returns an error.
+    ///
+    /// *Only enabled with the `image-input` feature.*
+    #[cfg(feature = "image-input")]
+    pub fn cover_image(mut self, image: &image::RgbImage) -> Self {
+        self.settings.cover_image = Some(image.clone());
+        self
+    }
+
+    /// When the video is finished, writes an [`EncodeSummary`] describing it and the settings
+    /// used to `path` as JSON, which is useful for keeping track of how a video was made.
+    ///
+    /// The sidecar is only written by [`SimpleVideoEncoder::finish`], not when switching to a
+    /// new file with [`SimpleVideoEncoder::reset_to`].
+    ///
+    /// *Only enabled with the `serde` feature.*
+    #[cfg(feature = "serde")]
+    pub fn write_sidecar<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.settings.sidecar_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set how long, in microseconds, the muxer may buffer packets while waiting for every
+    /// stream to have data so it can interleave them. Once a stream falls this far behind,
+    /// the buffered packets are written anyway. This is the muxer's `max_interleave_delta`
+    /// option, the same as the `ffmpeg` command line's `-max_interleave_delta`. It's not the
+    /// same as `-max_muxing_queue_size`, which limits how many packets the command line tool
+    /// queues for each stream before the muxer is opened, and has no library equivalent.
+    /// `0` means the muxer buffers without limit.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn max_interleave_delta(mut self, microseconds: i64) -> Self {
+        self.settings.max_interleave_delta = Some(microseconds);
+        self
+    }
+
+    /// If enabled, every packet is passed on to the output as soon as it's written, instead
+    /// of being buffered. This lowers latency when streaming, at the cost of more system
+    /// calls.
+    ///
+    /// By default, ffmpeg decides based on the output; it only flushes immediately for
+    /// outputs that can't seek, like network streams.
+    pub fn flush_packets(mut self, enable: bool) -> Self {
+        self.settings.flush_packets = Some(enable);
+        self
+    }
+
+    /// Set format-level flags for the muxer. See [`MuxerFlags`] for what each flag does and
+    /// which outputs it suits. Calling this again replaces the flags set before.
+    ///
+    /// No flags by default.
+    pub fn muxer_flags(mut self, flags: MuxerFlags) -> Self {
+        self.settings.muxer_flags = flags;
+        self
+    }
+
+    /// Set the disposition of the video stream, which players use to decide which stream to
+    /// show when a file has several, such as when it also has a cover image. Not every
+    /// container can store every flag; the muxer ignores those it can't.
+    ///
+    /// By default, the muxer decides; most mark the only video stream as the default.
+    pub fn video_disposition(mut self, disposition: StreamDisposition) -> Self {
+        self.settings.video_disposition = Some(disposition);
+        self
+    }
+
+    /// If enabled, appending a frame sleeps until the frame's time in the video has passed
+    /// since the first frame was appended, so frames are encoded no faster than real time.
+    /// This is useful for live previews and streams fed by a loop that can produce frames
+    /// faster than they should be shown. If frames are produced too slowly, nothing waits.
+    ///
+    /// Disabled by default.
+    pub fn realtime_pacing(mut self, enable: bool) -> Self {
+        self.settings.realtime_pacing = enable;
+        self
+    }
+
+    /// Encodes the video as interlaced, with the given field order. Each frame you provide
+    /// should contain both fields woven together, one in the even lines and the other in the
+    /// odd lines. Note that frames which need to be scaled are scaled as if they were
+    /// progressive, which mixes the fields together; provide frames that match the output
+    /// size to avoid this.
+    ///
+    /// Progressive by default.
+    pub fn interlaced(mut self, field_order: FieldOrder) -> Self {
+        self.settings.field_order = Some(field_order);
+        self
+    }
+
+    /// Sets a function to be called with each frame just before it is encoded, along with the
+    /// index of that frame in the video (or its timestamp, with [`Self::use_frame_pts`]). The
+    /// function may modify the frame, which is useful for drawing overlays.
+    ///
+    /// The frame is passed as it was given to [`SimpleVideoEncoder::append_frame`], before
+    /// any pixel format conversion. If the builder is cloned, encoders built from each clone
+    /// share the same function.
+    pub fn pre_encode(mut self, hook: impl FnMut(&mut Frame, u64) + Send + 'static) -> Self {
+        self.callbacks.pre_encode = Some(Arc::new(Mutex::new(hook)));
+        self
+    }
+
+    /// Sets a function to be called with statistics about each packet of compressed video
+    /// as it's produced, such as its size. This is useful for analyzing how the encoder
+    /// distributes bits between frames, and doesn't affect the output. If the builder is
+    /// cloned, encoders built from each clone share the same function.
+    pub fn on_packet(mut self, callback: impl FnMut(&PacketStats) + Send + 'static) -> Self {
+        self.callbacks.on_packet = Some(Arc::new(Mutex::new(callback)));
+        self
+    }
+
+    /// If enabled, the index of each frame is drawn in the top-left corner of the video.
+    /// This can be useful for debugging synchronization issues. It is drawn after any
+    /// [`Self::pre_encode`] hook has run, onto a copy of the frame, so the frame itself is
+    /// left unchanged. Only RGB24 frames, such as those returned by
+    /// [`SimpleVideoEncoder::new_frame`], are supported.
+    ///
+    /// Disabled by default.
+    ///
+    /// *Only enabled with the `cairo-input` feature.*
+    #[cfg(feature = "cairo-input")]
+    pub fn burn_frame_number(mut self, enable: bool) -> Self {
+        self.settings.burn_frame_number = enable;
+        self
+    }
+
+    /// If enabled, the directory containing the output file (and any of its missing parents)
+    /// is created when the encoder is built. Otherwise, building fails with
+    /// [`EncoderError::DirectoryMissing`] if it doesn't exist.
+    ///
+    /// Disabled by default.
+    pub fn create_dirs(mut self, enable: bool) -> Self {
+        self.settings.create_dirs = enable;
+        self
+    }
+
+    /// Produce a SimpleVideoEncoder using the specified settings.
+    pub fn build(self) -> Result<SimpleVideoEncoder, Box<dyn Error>> {
+        self.validate()?;
+        self.settings.prepare_output_dir(&self.filename)?;
+
+        let format_context = open_output(
+            Some(&self.filename),
+            &[],
+            self.width,
+            self.height,
+            self.framerate,
+            &self.settings,
+            self.callbacks,
+        )?;
+
+        Ok(SimpleVideoEncoder {
+            width: self.width,
+            height: self.height,
+            framerate: self.framerate,
+            settings: self.settings,
+            format_context,
+        })
+    }
+
+    /// Opens the encoder with the specified settings and reads back what it resolved them
+    /// to, including defaults for anything that wasn't set, such as the GOP size or the
+    /// number of B-frames. Nothing is written to the output file.
+    pub fn effective_settings(&self) -> Result<EffectiveSettings, Box<dyn Error>> {
+        self.validate()?;
+
+        let output = open_output(
+            None,
+            &[],
+            self.width,
+            self.height,
+            self.framerate,
+            &self.settings,
+            Callbacks::default(),
+        )?;
+
+        Ok(output.effective_settings())
+    }
+
+    /// Produce a [`PacketEncoder`] using the specified settings, which returns the encoded
+    /// packets instead of writing them to a file. The filename and any settings that only
+    /// affect the container are ignored.
+    pub fn build_packet_encoder(self) -> Result<PacketEncoder, Box<dyn Error>> {
+        self.validate()?;
+
+        let output = open_output(
+            None,
+            &[],
+            self.width,
+            self.height,
+            self.framerate,
+            &self.settings,
+            self.callbacks,
+        )?;
+
+        Ok(PacketEncoder::from_output(self.width, self.height, output))
+    }
+
+    /// Produce a [`TeeEncoder`] using the specified settings, which writes the same encoded
+    /// video to this builder's file and to each of `outputs`, without encoding it again for
+    /// each one.
+    pub fn build_tee(self, outputs: &[TeeOutput]) -> Result<TeeEncoder, Box<dyn Error>> {
+        self.validate()?;
+        self.settings.prepare_output_dir(&self.filename)?;
+
+        let format_context = open_output(
+            Some(&self.filename),
+            outputs,
+            self.width,
+            self.height,
+            self.framerate,
+            &self.settings,
+            self.callbacks,
+        )?;
+
+        Ok(TeeEncoder::from_encoder(SimpleVideoEncoder {
+            width: self.width,
+            height: self.height,
+            framerate: self.framerate,
+            settings: self.settings,
+            format_context,
+        }))
+    }
+
+    /// Produce a [`VariableRateEncoder`] using the specified settings, where each frame is
+    /// timestamped with the instant it was captured at. The framerate sets the precision of
+    /// those timestamps, and [`Self::use_frame_pts`] is always enabled.
+    pub fn build_variable_rate(mut self) -> Result<VariableRateEncoder, Box<dyn Error>> {
+        self.settings.use_frame_pts = true;
+        Ok(VariableRateEncoder::from_encoder(self.build()?))
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if let Some(params) = self.settings.film_grain {
+            if self.settings.codec != Some(VideoCodec::Av1) {
+                return Err("Film grain synthesis is only supported with the AV1 codec".into());
+            }
+            if !(1..=50).contains(&params.strength) {
+                return Err("Film grain strength must be between 1 and 50".into());
+            }
+        }
+
+        if self.settings.hdr10.is_some() {
+            if !matches!(
+                self.settings.codec,
+                Some(VideoCodec::H265 | VideoCodec::Av1)
+            ) {
+                return Err("HDR10 is only supported with the H.265 and AV1 codecs".into());
+            }
+            if self.settings.bit_depth != Some(BitDepth::Ten) {
+                return Err("HDR10 requires 10-bit output".into());
+            }
+        }
+
+        if let Some((top, bottom, left, right)) = self.settings.display_crop {
+            if self.settings.codec.unwrap_or(VideoCodec::H264) != VideoCodec::H264 {
+                return Err("Display cropping is only supported with the H.264 codec".into());
+            }
+            if u64::from(top) + u64::from(bottom) >= self.height as u64
+                || u64::from(left) + u64::from(right) >= self.width as u64
+            {
+                return Err("Display crop would leave nothing to show".into());
+            }
+            // Cropping is coded in units of chroma samples.
+            let (horizontal_unit, vertical_unit) = match self.settings.chroma_subsampling {
+                None | Some(ChromaSubsampling::Yuv420) => (2, 2),
+                Some(ChromaSubsampling::Yuv422) => (2, 1),
+                Some(ChromaSubsampling::Yuv444) => (1, 1),
+            };
+            if left % horizontal_unit != 0
+                || right % horizontal_unit != 0
+                || top % vertical_unit != 0
+                || bottom % vertical_unit != 0
+            {
+                return Err("Display crop must be a whole number of chroma samples".into());
+            }
+        }
+
+        if self.settings.alpha {
+            if self.settings.codec != Some(VideoCodec::Vp9) {
+                return Err("Alpha is only supported with the VP9 codec".into());
+            }
+            if self.settings.bit_depth == Some(BitDepth::Ten)
+                || !matches!(
+                    self.settings.chroma_subsampling,
+                    None | Some(ChromaSubsampling::Yuv420)
+                )
+            {
+                return Err("Alpha is only supported with 8-bit 4:2:0 output".into());
+            }
+        }
+
+        if self.framerate.0 <= 0 || self.framerate.1 <= 0 {
+            return Err("Framerate must be positive".into());
+        }
+
+        if self.settings.keyframe_interval.is_some() && self.settings.keyframe_every.is_some() {
+            return Err(
+                "keyframe_interval_seconds and keyframe_every can't be used together".into(),
+            );
+        }
+        if let Some(seconds) = self.settings.keyframe_interval {
+            if !(seconds.is_finite() && seconds > 0.0) {
+                return Err("Keyframe interval must be a positive number of seconds".into());
+            }
+        }
+
+        if self.settings.bit_depth == Some(BitDepth::Ten)
+            && self.settings.codec == Some(VideoCodec::Gif)
+        {
+            return Err("GIFs only support 8-bit output".into());
+        }
+
+        if let Some(amount) = self.settings.sws_sharpen {
+            if !(amount.is_finite() && amount >= 0.0) {
+                return Err("Sharpening amount must be a non-negative number".into());
+            }
+        }
+
+        if let Some(strength) = self.settings.denoise {
+            if !(strength.is_finite() && strength > 0.0) {
+                return Err("Denoising strength must be a positive number".into());
+            }
+        }
+
+        if self.settings.gif_dither.is_some() && self.settings.codec != Some(VideoCodec::Gif) {
+            return Err("Dithering is only supported with the GIF codec".into());
+        }
+
+        // Constant QP overrides CRF, so the CRF doesn't matter then.
+        if let (Some(crf), None) = (self.settings.crf, self.settings.qp) {
+            let codec = self.settings.codec.unwrap_or(VideoCodec::H264);
+            if !codec.crf_range().is_some_and(|range| range.contains(&crf)) {
+                return Err(EncoderError::InvalidCrf(crf).into());
+            }
+        }
+
+        for (start, end, _) in &self.settings.chapters {
+            if !(start.is_finite() && end.is_finite() && *start >= 0.0 && end > start) {
+                return Err("Chapters must end after they start, at non-negative times".into());
+            }
+        }
+
+        if let Some(brand) = &self.settings.mp4_brand {
+            if brand.len() != 4 || !brand.is_ascii() {
+                return Err("MP4 brands must be four ASCII characters".into());
+            }
+        }
+
+        let motion_settings =
+            self.settings.motion_estimation.is_some() || self.settings.me_range.is_some();
+        if motion_settings && self.settings.codec.unwrap_or(VideoCodec::H264) != VideoCodec::H264 {
+            return Err("Motion estimation settings are only supported with H.264".into());
+        }
+        if self.settings.me_range.is_some_and(|range| range < 4) {
+            return Err("Motion search range must be at least 4".into());
+        }
+
+        if let Some(min_gop_size) = self.settings.min_gop_size {
+            if self.settings.codec.unwrap_or(VideoCodec::H264) != VideoCodec::H264 {
+                return Err("Minimum GOP size is only supported with H.264".into());
+            }
+            let gop_size = self.settings.gop_frames(self.framerate);
+            if !(1..=gop_size).contains(&min_gop_size) {
+                return Err(format!(
+                    "Minimum GOP size must be between 1 and the GOP size ({})",
+                    gop_size
+                )
+                .into());
+            }
+        }
+
+        if self
+            .settings
+            .initial_buffer_occupancy
+            .is_some_and(|bits| !(1..=i32::MAX as i64).contains(&bits))
+        {
+            return Err("Initial buffer occupancy must be between 1 and i32::MAX bits".into());
+        }
+
+        if let Some(trellis) = self.settings.trellis {
+            if self.settings.codec.unwrap_or(VideoCodec::H264) != VideoCodec::H264 {
+                return Err("Trellis quantization is only supported with H.264".into());
+            }
+            if trellis > 2 {
+                return Err("Trellis mode must be between 0 and 2".into());
+            }
+        }
+
+        if self.settings.disable_psy
+            && !matches!(
+                self.settings.codec.unwrap_or(VideoCodec::H264),
+                VideoCodec::H264 | VideoCodec::H265
+            )
+        {
+            return Err(
+                "Disabling psychovisual optimizations is only supported with H.264 and H.265"
+                    .into(),
+            );
+        }
+
+        if self.settings.start_pts.is_some_and(|pts| pts < 0) {
+            return Err("Start PTS must not be negative".into());
+        }
+
+        if let Some(factor) = self.settings.b_quant_factor {
+            if !(factor.is_finite() && factor > 0.0) {
+                return Err("B-frame quantizer factor must be a positive number".into());
+            }
+        }
+
+        if self.settings.max_qdiff.is_some_and(|qdiff| qdiff < 1) {
+            return Err("Maximum quantizer difference must be positive".into());
+        }
+
+        if let Some(ref_frames) = self.settings.ref_frames {
+            if !(1..=16).contains(&ref_frames) {
+                return Err("Reference frame count must be between 1 and 16".into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn open_output(
+    filename: Option<&Path>,
+    extra_outputs: &[TeeOutput],
+    width: i32,
+    height: i32,
+    framerate: (i32, i32),
+    settings: &OptionalSettings,
+    callbacks: Callbacks,
+) -> Result<OutputStream, Box<dyn Error>> {
+    let video_codec = settings.codec.unwrap_or(VideoCodec::H264);
+    // GIFs are palettized rather than YUV; 3-3-2 bit RGB gives a fixed 256 color palette.
+    let pixel_format = if video_codec == VideoCodec::Gif {
+        AVPixelFormat::AV_PIX_FMT_RGB8
+    } else if settings.alpha {
+        AVPixelFormat::AV_PIX_FMT_YUVA420P
+    } else {
+        settings
+            .chroma_subsampling
+            .unwrap_or(ChromaSubsampling::Yuv420)
+            .as_av(settings.bit_depth.unwrap_or(BitDepth::Eight))
+    };
+
+    let mut output = OutputStream::new(
+        filename,
+        video_codec,
+        width,
+        height,
+        framerate,
+        pixel_format,
+        settings,
+    )?;
+
+    for extra_output in extra_outputs {
+        output.add_output(extra_output, settings)?;
+    }
+    output.open(settings)?;
+    output.set_callbacks(callbacks);
+
+    Ok(output)
+}
+
+/// A description of a video written by [`SimpleVideoEncoder::encode_images`].
+///
+/// *Only enabled with the `image-input` feature.*
+#[cfg(feature = "image-input")]
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct ImageSequenceSummary {
+    /// The width of the video in pixels, taken from the images.
+    pub width: u32,
+    /// The height of the video in pixels, taken from the images.
+    pub height: u32,
+    /// The number of frames in the video, one per image.
+    pub frame_count: u64,
+    /// The length of the video in seconds.
+    pub duration_seconds: f64,
+    /// The size of the finished file in bytes.
+    pub file_size: u64,
+}
+
+/// A simple video encoder that can accept frames of video and will write them into a video file.
+pub struct SimpleVideoEncoder {
+    width: i32,
+    height: i32,
+    framerate: (i32, i32),
+    // kept so that the same settings can be used again by reset_to
+    settings: OptionalSettings,
+
+    format_context: OutputStream,
+}
+impl SimpleVideoEncoder {
+    /// Creates a SimpleVideoEncoder targeting the specified file name with default settings.
+    /// The container format will be detected automatically using the file extension.
+    pub fn new<P: AsRef<Path>>(
+        filename: P,
+        width: i32,
+        height: i32,
+        framerate: i32,
+    ) -> Result<Self, Box<dyn Error>> {
+        SimpleVideoEncoderBuilder::new(filename, width, height, framerate).build()
+    }
+
+    /// Creates a SimpleVideoEncoder with default settings which writes to a new temporary file.
+    /// Since the temporary file has no extension, the container format must be given by
+    /// name, such as `"mp4"`.
+    ///
+    /// The temporary file is deleted when the returned handle is dropped, unless it is
+    /// persisted first. Make sure to call [`Self::finish`] before using the file.
+    ///
+    /// *Only enabled with the `tempfile` feature.*
+    #[cfg(feature = "tempfile")]
+    pub fn new_to_temp(
+        format_name: &str,
+        width: i32,
+        height: i32,
+        framerate: i32,
+    ) -> Result<(Self, tempfile::NamedTempFile), Box<dyn Error>> {
+        let file = tempfile::NamedTempFile::new()?;
+        let encoder = SimpleVideoEncoderBuilder::new(file.path(), width, height, framerate)
+            .container_format(format_name)
+            .build()?;
+        Ok((encoder, file))
+    }
+
+    /// Encodes a single image as a one-frame video, such as a poster image, and finishes the
+    /// file. The video has the same size as the image; note that most codecs require the
+    /// width and height to be even.
+    ///
+    /// *Only enabled with the `image-input` feature.*
+    #[cfg(feature = "image-input")]
+    pub fn encode_single_image<P: AsRef<Path>>(
+        filename: P,
+        image: &image::RgbImage,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut encoder =
+            SimpleVideoEncoder::new(filename, image.width() as i32, image.height() as i32, 1)?;
+
+        let mut frame = encoder.new_frame()?;
+        frame.fill_from_image_rgb(image)?;
+        encoder.append_frame(&mut frame)?;
+
+        encoder.finish()
+    }
+
+    /// Encodes each image as one frame of a video, and finishes the file. The video has the
+    /// same size as the images, so they must all be the same size; note that most codecs
+    /// require the width and height to be even.
+    ///
+    /// All of the images are checked before the file is created, so nothing is written if
+    /// any of them don't match.
+    ///
+    /// *Only enabled with the `image-input` feature.*
+    #[cfg(feature = "image-input")]
+    pub fn encode_images<P: AsRef<Path>>(
+        filename: P,
+        images: &[image::RgbImage],
+        framerate: i32,
+    ) -> Result<ImageSequenceSummary, Box<dyn Error>> {
+        let first = images.first().ok_or("No images to encode")?;
+        let (width, height) = first.dimensions();
+        if let Some((index, image)) = images
+            .iter()
+            .enumerate()
+            .find(|(_, image)| image.dimensions() != (width, height))
+        {
+            return Err(format!(
+                "Image {} is {}x{}, but the first image is {}x{}",
+                index,
+                image.width(),
+                image.height(),
+                width,
+                height
+            )
+            .into());
+        }
+
+        let filename = filename.as_ref();
+        let mut encoder =
+            SimpleVideoEncoder::new(filename, width as i32, height as i32, framerate)?;
+
+        let mut frame = encoder.new_frame()?;
+        for image in images {
+            frame.fill_from_image_rgb(image)?;
+            encoder.append_frame(&mut frame)?;
+        }
+        encoder.finish()?;
+
+        Ok(ImageSequenceSummary {
+            width,
+            height,
+            frame_count: images.len() as u64,
+            duration_seconds: images.len() as f64 / framerate as f64,
+            file_size: fs::metadata(filename)?.len(),
+        })
+    }
+
+    /// Creates a SimpleVideoEncoder with default settings which streams to a network URL,
+    /// such as `rtmp://example.com/live/key`, instead of writing a file. Since the container
+    /// format can't be detected from a URL, it must be given by name; RTMP servers expect
+    /// `"flv"`, and `"mpegts"` works for UDP or SRT.
+    ///
+    /// The output can't be seeked, so choose a format that doesn't need to go back and
+    /// update the start of the file when finishing (mp4, for example, won't work). When
+    /// streaming with `"flv"`, the duration and file size are not written into the header.
+    pub fn new_stream(
+        url: &str,
+        format_name: &str,
+        width: i32,
+        height: i32,
+        framerate: i32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut builder = SimpleVideoEncoderBuilder::new(url, width, height, framerate)
+            .container_format(format_name);
+        builder.settings.streaming = true;
+        if format_name == "flv" {
+            // Otherwise the muxer tries to seek back to the header when finishing.
+            builder = builder.muxer_option("flvflags", "no_duration_filesize");
+        }
+        builder.build()
+    }
+
+    /// Creates a SimpleVideoEncoder which writes an HLS playlist to `playlist_path` (which
+    /// should end in `.m3u8`), split into MPEG-TS segments of `segment_duration` seconds.
+    /// The segments are written next to the playlist and named after it, so `video.m3u8`
+    /// gets `video_000.ts`, `video_001.ts`, and so on.
+    ///
+    /// A keyframe is placed at the start of every segment, and nowhere else.
+    pub fn new_hls<P: AsRef<Path>>(
+        playlist_path: P,
+        segment_duration: u32,
+        width: i32,
+        height: i32,
+        framerate: i32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let playlist_path = playlist_path.as_ref();
+        let stem = playlist_path
+            .file_stem()
+            .ok_or("HLS playlist path has no file name")?
+            .to_string_lossy();
+        let segment_path = playlist_path.with_file_name(format!("{}_%03d.ts", stem));
+        let gop_size = i32::try_from(segment_duration)
+            .ok()
+            .and_then(|duration| framerate.checked_mul(duration))
+            .filter(|&gop_size| gop_size > 0)
+            .ok_or("HLS segments must be at least one frame and at most i32::MAX frames long")?;
+
+        SimpleVideoEncoderBuilder::new(playlist_path, width, height, framerate)
+            .container_format("hls")
+            .set_gop_size(gop_size)
+            .fixed_gop(true)
+            .muxer_option("hls_time", segment_duration.to_string())
+            .muxer_option("hls_segment_filename", segment_path.to_string_lossy())
+            // Keep every segment in the playlist, rather than only the most recent few.
+            .muxer_option("hls_list_size", "0")
+            .build()
+    }
+
+    /// Produces a builder targeting the specified file name, which allows specifying additional settings.
+    /// The container format will be detected automatically using the file extension.
+    pub fn builder<P: AsRef<Path>>(
+        filename: P,
+        width: i32,
+        height: i32,
+        framerate: i32,
+    ) -> SimpleVideoEncoderBuilder {
+        SimpleVideoEncoderBuilder::new(filename, width, height, framerate)
+    }
+
+    /// Finishes encoding the video and writes any trailer required by the container format.
+    /// (Note that mp4 has a required trailer.)
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.format_context.finish()?;
+
+        #[cfg(feature = "serde")]
+        if let Some(path) = &self.settings.sidecar_path {
+            EncodeSummary::new(
+                &self.settings,
+                self.width,
+                self.height,
+                self.framerate,
+                self.format_context.frame_count(),
+                self.format_context.duration_seconds(),
+                self.format_context.output_size(),
+            )
+            .write(path)?;
+        }
+        Ok(())
+    }
+
+    /// Encodes frames from `producer` until it returns `None`, and then finishes the video.
+    /// The producer is called with the index of the frame it should produce, starting at 0.
+    ///
+    /// Each frame is moved out of the producer, so it needs to allocate a new one every time,
+    /// for example with [`Frame::new`].
+    pub fn run<F: FnMut(u64) -> Option<Frame>>(
+        mut self,
+        mut producer: F,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut index = 0;
+        while let Some(mut frame) = producer(index) {
+            self.append_frame(&mut frame)?;
+            index += 1;
+        }
+
+        self.finish()
+    }
+
+    /// Like [`Self::run`], but takes frames from two producers and shows them next to each
+    /// other, which is useful for comparing two versions of a video. Encoding stops when either
+    /// producer returns `None`.
+    ///
+    /// Frames must be RGB24, and at most half the size of the video in the direction they're
+    /// split along: for [`ComparisonLayout::LeftRight`], a 1280x360 video takes 640x360 frames.
+    pub fn run_comparison<A, B>(
+        mut self,
+        layout: ComparisonLayout,
+        mut producer_a: A,
+        mut producer_b: B,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        A: FnMut(u64) -> Option<Frame>,
+        B: FnMut(u64) -> Option<Frame>,
+    {
+        let (offset_x, offset_y) = match layout {
+            ComparisonLayout::LeftRight => (self.width / 2, 0),
+            ComparisonLayout::TopBottom => (0, self.height / 2),
+        };
+
+        let mut output = self.new_frame()?;
+        // Smaller frames don't cover the whole video, so the rest is left black.
+        output.fill_with(|_, _| [0, 0, 0])?;
+        let mut index = 0;
+        while let (Some(a), Some(b)) = (producer_a(index), producer_b(index)) {
+            output.copy_into_region(&a, 0, 0)?;
+            output.copy_into_region(&b, offset_x, offset_y)?;
+            self.append_frame(&mut output)?;
+            index += 1;
+        }
+
+        self.finish()
+    }
+
+    /// Finishes the current video, exactly like [`Self::finish`], and then starts writing a
+    /// new one to `filename` with the same settings. This saves repeating the configuration
+    /// when producing many videos in a batch.
+    ///
+    /// Every setting from the builder carries over, including the container format (so
+    /// make sure the new filename suits it), the callbacks, and the frame size. The
+    /// encoder itself is recreated, since encoders like x264 can't be restarted once
+    /// they've been flushed, and timestamps, timing stats, and duplicate detection start
+    /// over. If this fails, appending more frames returns [`EncoderError::InvalidState`].
+    pub fn reset_to<P: AsRef<Path>>(&mut self, filename: P) -> Result<(), Box<dyn Error>> {
+        self.format_context.finish()?;
+        self.settings.prepare_output_dir(filename.as_ref())?;
+
+        let callbacks = self.format_context.take_callbacks();
+        self.format_context = open_output(
+            Some(filename.as_ref()),
+            &[],
+            self.width,
+            self.height,
+            self.framerate,
+            &self.settings,
+            callbacks,
+        )?;
+
+        Ok(())
+    }
+
+    /// Adds the data in the frame as the video's next frame. This may mutate the frame.
+    /// After calling this, you may freely reuse the frame buffer.
+    pub fn append_frame(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error>> {
+        self.format_context
+            .write_frame(frame, FrameExtras::default())
+    }
+
+    /// Like [`Self::append_frame`], but takes the frame by value and hands it back once it
+    /// has been encoded, ready to be filled again. This makes it easy to cycle a few frames
+    /// between the code producing them and the encoder. If encoding fails, the frame is
+    /// dropped along with the error.
+    pub fn append_owned(&mut self, mut frame: Frame) -> Result<Frame, Box<dyn Error>> {
+        self.append_frame(&mut frame)?;
+        Ok(frame)
+    }
+
+    /// Encodes `frame_count` frames of tightly-packed RGB24 data, one after the other, such as
+    /// the contents of a memory-mapped file of raw frames. Each frame must be exactly
+    /// `width * height * 3` bytes, matching the size of the video. A single frame buffer is
+    /// reused for all of the frames.
+    pub fn encode_raw_stream(
+        &mut self,
+        data: &[u8],
+        frame_count: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        let row_size = self.width as usize * 3;
+        let frame_size = row_size * self.height as usize;
+        if data.len() != frame_size * frame_count {
+            return Err(format!(
+                "Raw stream is {} bytes, but {} frames of {} bytes each were expected",
+                data.len(),
+                frame_count,
+                frame_size
+            )
+            .into());
+        }
+
+        let mut frame = self.new_frame()?;
+        for frame_data in data.chunks_exact(frame_size) {
+            frame.fill_from_raw_planar(&[frame_data], &[row_size])?;
+            self.append_frame(&mut frame)?;
+        }
+
+        Ok(())
+    }
+
+    /// Converts the image to a frame and adds it as the video's next frame. Images of any
+    /// color type and size are accepted; they're converted to RGB, and scaled to the size of
+    /// the video if needed.
+    ///
+    /// This allocates a new frame for each image. When encoding many images of the same
+    /// type, filling a reused frame with [`Frame::fill_from_image_rgb`] avoids that.
+    ///
+    /// *Only enabled with the `image-input` feature.*
+    #[cfg(feature = "image-input")]
+    pub fn append_image(
+        &mut self,
+        image: impl Into<image::DynamicImage>,
+    ) -> Result<(), Box<dyn Error>> {
+        let image = image.into().into_rgb8();
+
+        let mut frame = Frame::new(
+            PixelFormat::Rgb24,
+            image.width() as i32,
+            image.height() as i32,
+        )?;
+        frame.fill_from_image_rgb(&image)?;
+        self.append_frame(&mut frame)
+    }
+
+    /// Adds a frame to the video from raw pixel data laid out as described by `format`. The
+    /// data is converted to the video's pixel format, and scaled if its size is different.
+    ///
+    /// This is the most general way to add frames which come from outside of Rust, such as
+    /// from files written by other tools. It allocates a new frame every time; if the
+    /// format is known ahead of time, filling a reused frame with
+    /// [`Frame::fill_from_raw_planar`] avoids that.
+    pub fn append_raw(
+        &mut self,
+        data: &[u8],
+        format: &RawFrameFormat,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut frame = Frame::new(format.pixel_format, format.width, format.height)?;
+        frame.fill_from_raw_contiguous(data, format.stride)?;
+        self.append_frame(&mut frame)
+    }
+
+    /// Adds `frames` frames to the video which show `frame` fading out to black, ending on a
+    /// completely black frame. Only RGB24 frames, like those from [`Self::new_frame`], are
+    /// supported. The given frame is not modified.
+    pub fn append_fade_out(&mut self, frame: &Frame, frames: u32) -> Result<(), Box<dyn Error>> {
+        let mut faded = Frame::new(PixelFormat::Rgb24, frame.width(), frame.height())?;
+        for step in 1..=frames {
+            let brightness = 1.0 - step as f32 / frames as f32;
+            faded.scaled_from(frame, brightness)?;
+            self.append_frame(&mut faded)?;
+        }
+
+        Ok(())
+    }
+
+    /// Like [`Self::append_frame`], but also embeds `sei` in the video alongside this frame as
+    /// an H.264 "user data unregistered" SEI message, which can be read back by decoders.
+    /// The first 16 bytes of `sei` must be a UUID identifying the format of the rest of the
+    /// data.
+    ///
+    /// Only supported with the H.264 codec; with other codecs, an error is returned. Frames
+    /// with SEI data are never skipped by
+    /// [`SimpleVideoEncoderBuilder::skip_duplicate_frames`].
+    pub fn append_frame_with_sei(
+        &mut self,
+        frame: &mut Frame,
+        sei: &[u8],
+    ) -> Result<(), Box<dyn Error>> {
+        if self.settings.codec.unwrap_or(VideoCodec::H264) != VideoCodec::H264 {
+            return Err("SEI data is only supported with H.264".into());
+        }
+        if sei.len() < 16 {
+            return Err("SEI data must start with a 16-byte UUID".into());
+        }
+        self.format_context.write_frame(
+            frame,
+            FrameExtras {
+                sei: Some(sei),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Like [`Self::append_frame`], but asks the encoder to spend more or fewer bits on this
+    /// frame than it otherwise would, for example to keep the first frame of a new scene
+    /// sharp. `quality` ranges from -1.0 (much lower quality) to 1.0 (much higher quality),
+    /// with 0.0 making no change.
+    ///
+    /// This is applied on top of the normal rate control, and works best in a quality-based
+    /// mode like CRF. Supported by the H.264, H.265, and VP9 codecs; the others ignore it.
+    pub fn append_frame_with_quality(
+        &mut self,
+        frame: &mut Frame,
+        quality: f32,
+    ) -> Result<(), Box<dyn Error>> {
+        if !(-1.0..=1.0).contains(&quality) {
+            return Err("Frame quality must be between -1.0 and 1.0".into());
+        }
+        self.format_context.write_frame(
+            frame,
+            FrameExtras {
+                // A lower quantizer means higher quality.
+                quantizer_offset: Some(-quality),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// The number of frames the encoder delays its output by, for example because of frame
+    /// reordering when B-frames are used. This is needed to line up other streams, like
+    /// audio, with the video.
+    pub fn encoder_delay(&self) -> i64 {
+        self.format_context.encoder_delay()
+    }
+
+    /// The framerate of the output as ffmpeg resolved it once the file was opened, as a
+    /// numerator and denominator. This can be compared against the requested framerate to
+    /// check that it was kept exactly, such as 24000/1001 rather than 24. If the muxer doesn't
+    /// record a framerate, this is the timestamp precision it chose instead, such as 1000 for
+    /// Matroska; the same goes for [`SimpleVideoEncoderBuilder::use_frame_pts`], where the
+    /// output has no fixed framerate.
+    pub fn effective_framerate(&self) -> (i32, i32) {
+        self.format_context.effective_framerate()
+    }
+
+    /// Whether the output can be seeked, such as a regular file. Pipes and network streams
+    /// usually can't be, which some container formats need in order to finish the file.
+    pub fn is_seekable(&self) -> bool {
+        self.format_context.is_seekable()
+    }
+
+    /// The number of bytes written to the output so far, which is useful for showing progress
+    /// or stopping at a size limit. The muxer buffers its output, so some of these may not
+    /// have reached the file yet, and finishing the video adds a little more.
+    pub fn bytes_written(&self) -> u64 {
+        self.format_context.bytes_written()
+    }
+
+    /// The indices of the frames the encoder has made keyframes so far, in the order it
+    /// produced them, counted the same way as [`PacketStats::frame_index`]. With [`SimpleVideoEncoderBuilder::use_frame_pts`], these are the frames'
+    /// timestamps instead. Encoders buffer frames, so the most recent keyframes may only show
+    /// up after more frames have been appended.
+    ///
+    /// This is useful for checking that keyframes are placed as configured, such as with
+    /// [`SimpleVideoEncoderBuilder::keyframe_every`].
+    pub fn keyframe_indices(&self) -> &[u64] {
+        self.format_context.keyframe_indices()
+    }
+
+    /// Makes the final frame last for `frames` frames instead of one, so the video's total
+    /// duration includes it, for example to hold the last image of a slideshow. `0` is
+    /// treated as `1`.
+    ///
+    /// This must be called before the final frame is appended, since the encoder only keeps
+    /// a copy of each frame while it's set. The extra time is added by [`Self::finish`].
+    pub fn set_final_frame_duration(&mut self, frames: u32) {
+        self.format_context.set_final_frame_duration(frames);
+    }
+
+    /// The number of frames skipped so far because the encoder rejected them. Always 0
+    /// unless [`SimpleVideoEncoderBuilder::skip_bad_frames`] is enabled.
+    pub fn dropped_frames(&self) -> u64 {
+        self.format_context.dropped_frames()
+    }
+
+    /// Summarizes how long each frame took to encode so far. Returns `None` unless
+    /// [`SimpleVideoEncoderBuilder::collect_timing`] was enabled and at least one frame has
+    /// been encoded.
+    pub fn timing_stats(&self) -> Option<TimingStats> {
+        self.format_context.timing_stats()
+    }
+
+    /// Creates a new frame buffer which can be filled with your data and then given to
+    /// [`Self::append_frame`]. With [`SimpleVideoEncoderBuilder::with_alpha`], this is an
+    /// RGBA frame.
+    pub fn new_frame(&self) -> Result<Frame, Box<dyn Error>> {
+        Frame::new(self.new_frame_format(), self.width, self.height)
+    }
+
+    fn new_frame_format(&self) -> PixelFormat {
+        if self.settings.alpha {
+            PixelFormat::Rgba
+        } else {
+            PixelFormat::Rgb24
+        }
+    }
+
+    /// Moves this encoder onto a background thread, returning an [`AsyncEncoder`] which
+    /// accepts frames without blocking the caller. At most `queue_size` frames will be
+    /// queued before sending another frame blocks.
+    pub fn into_async(self, queue_size: usize) -> AsyncEncoder {
+        AsyncEncoder::new(self, queue_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn builder() -> SimpleVideoEncoderBuilder {
+        SimpleVideoEncoder::builder("test.mp4", 64, 64, 30)
+    }
+
+    #[test]
+    fn default_settings_are_valid() {
+        builder().validate().unwrap();
+    }
+
+    #[test]
+    fn rate_control_replaces_earlier_modes() {
+        let builder = builder()
+            .crf(100)
+            .rate_control(RateControl::Bitrate(1_000_000));
+        assert_eq!(builder.settings.crf, None);
+        assert_eq!(builder.settings.bitrate, Some(1_000_000));
+        builder.validate().unwrap();
+
+        let builder = builder.rate_control(RateControl::ConstantQp(20));
+        assert_eq!(builder.settings.bitrate, None);
+        assert_eq!(builder.settings.qp, Some(20));
+    }
+
+    #[test]
+    fn hls_segment_duration_must_give_a_valid_gop() {
+        assert!(SimpleVideoEncoder::new_hls("test.m3u8", 0, 64, 64, 30).is_err());
+        assert!(SimpleVideoEncoder::new_hls("test.m3u8", u32::MAX, 64, 64, 30).is_err());
+        assert!(SimpleVideoEncoder::new_hls("test.m3u8", i32::MAX as u32, 64, 64, 2).is_err());
+    }
+
+    #[test]
+    fn keyframe_interval_must_be_positive() {
+        builder().keyframe_interval_seconds(2.0).validate().unwrap();
+        assert!(builder().keyframe_interval_seconds(0.0).validate().is_err());
+        assert!(builder()
+            .keyframe_interval_seconds(f64::INFINITY)
+            .validate()
+            .is_err());
+        assert!(builder().rational_framerate(30, 0).validate().is_err());
+    }
+
+    #[test]
+    fn keyframe_interval_sets_gop_in_frames() {
+        let builder = builder()
+            .rational_framerate(30000, 1001)
+            .keyframe_interval_seconds(2.0);
+        // 2 seconds at 29.97 frames per second
+        assert_eq!(builder.settings.gop_frames(builder.framerate), 60);
+    }
+
+    #[test]
+    fn crf_must_be_in_codec_range() {
+        builder().crf(51).validate().unwrap();
+        let err = builder().crf(52).validate().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<EncoderError>(),
+            Some(EncoderError::InvalidCrf(52))
+        ));
+        builder().codec(VideoCodec::Vp9).crf(63).validate().unwrap();
+        assert!(builder().codec(VideoCodec::Gif).crf(10).validate().is_err());
+    }
+
+    #[test]
+    fn keyframe_interval_settings_are_exclusive() {
+        builder()
+            .keyframe_every(Duration::from_secs(2))
+            .validate()
+            .unwrap();
+        assert!(builder()
+            .keyframe_interval_seconds(2.0)
+            .keyframe_every(Duration::from_secs(2))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn keyframe_every_and_gop_size_replace_each_other() {
+        let builder = builder()
+            .set_gop_size(10)
+            .keyframe_every(Duration::from_secs(2));
+        assert_eq!(builder.settings.gop_frames(builder.framerate), 60);
+        let builder = builder.set_gop_size(10);
+        assert_eq!(builder.settings.gop_frames(builder.framerate), 10);
+    }
+
+    #[test]
+    fn trellis_is_only_for_h264() {
+        builder().trellis(2).validate().unwrap();
+        assert!(builder().trellis(3).validate().is_err());
+        assert!(builder()
+            .codec(VideoCodec::Vp9)
+            .trellis(1)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn mp4_brand_must_be_four_ascii_characters() {
+        builder().mp4_brand("isom").validate().unwrap();
+        assert!(builder().mp4_brand("mp4").validate().is_err());
+        assert!(builder().mp4_brand("mp42x").validate().is_err());
+        assert!(builder().mp4_brand("äbc").validate().is_err());
+    }
+
+    #[test]
+    fn alpha_needs_8_bit_420_vp9() {
+        builder()
+            .codec(VideoCodec::Vp9)
+            .with_alpha(true)
+            .validate()
+            .unwrap();
+        assert!(builder().with_alpha(true).validate().is_err());
+        assert!(builder()
+            .codec(VideoCodec::Vp9)
+            .with_alpha(true)
+            .bit_depth(BitDepth::Ten)
+            .validate()
+            .is_err());
+        assert!(builder()
+            .codec(VideoCodec::Vp9)
+            .with_alpha(true)
+            .chroma_subsampling(ChromaSubsampling::Yuv444)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn start_pts_must_not_be_negative() {
+        builder().start_pts(0).validate().unwrap();
+        assert!(builder().start_pts(-1).validate().is_err());
+    }
+
+    #[test]
+    fn min_gop_size_must_fit_in_gop() {
+        builder()
+            .set_gop_size(30)
+            .min_gop_size(30)
+            .validate()
+            .unwrap();
+        assert!(builder()
+            .set_gop_size(30)
+            .min_gop_size(31)
+            .validate()
+            .is_err());
+        assert!(builder()
+            .set_gop_size(30)
+            .min_gop_size(0)
+            .validate()
+            .is_err());
+        // 2 seconds at 30 frames per second
+        builder()
+            .keyframe_interval_seconds(2.0)
+            .min_gop_size(60)
+            .validate()
+            .unwrap();
+        assert!(builder()
+            .codec(VideoCodec::Vp9)
+            .min_gop_size(5)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn initial_buffer_occupancy_must_fit_in_i32() {
+        builder()
+            .initial_buffer_occupancy(i32::MAX as i64)
+            .validate()
+            .unwrap();
+        assert!(builder().initial_buffer_occupancy(0).validate().is_err());
+        assert!(builder()
+            .initial_buffer_occupancy(i32::MAX as i64 + 1)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn denoise_strength_must_be_positive() {
+        builder().denoise(0.5).validate().unwrap();
+        assert!(builder().denoise(0.0).validate().is_err());
+        assert!(builder().denoise(f32::NAN).validate().is_err());
     }
 }
@@ -0,0 +1,66 @@
+use std::sync::Once;
+
+use ffmpeg_sys_next::{
+    av_log_get_level, av_log_set_level, AV_LOG_DEBUG, AV_LOG_ERROR, AV_LOG_INFO, AV_LOG_QUIET,
+    AV_LOG_VERBOSE, AV_LOG_WARNING,
+};
+
+// Completed either by the first encoder silencing libav, or by the user choosing a level,
+// so a level chosen by the user is never overwritten.
+static LOG_LEVEL_SET: Once = Once::new();
+
+/// How much ffmpeg's own libraries log to stderr. See [`set_log_level`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LogLevel {
+    /// Print nothing. This is the default used by this crate.
+    Quiet,
+    /// Only print errors.
+    Error,
+    /// Print errors and warnings.
+    Warning,
+    /// Print informational messages, such as encoder settings. This is ffmpeg's default.
+    Info,
+    /// Print detailed information about what ffmpeg is doing.
+    Verbose,
+    /// Print everything, including debugging information.
+    Debug,
+}
+impl LogLevel {
+    fn as_av(&self) -> i32 {
+        match self {
+            LogLevel::Quiet => AV_LOG_QUIET,
+            LogLevel::Error => AV_LOG_ERROR as i32,
+            LogLevel::Warning => AV_LOG_WARNING as i32,
+            LogLevel::Info => AV_LOG_INFO as i32,
+            LogLevel::Verbose => AV_LOG_VERBOSE as i32,
+            LogLevel::Debug => AV_LOG_DEBUG as i32,
+        }
+    }
+}
+
+/// Sets ffmpeg's global log level.
+///
+/// By default, the first encoder created silences ffmpeg's logging entirely, unless the
+/// level has already been changed from ffmpeg's default, such as by other code using
+/// ffmpeg. Calling this (before or after creating encoders) opts out of that, and the level
+/// given here is left alone from then on. Since the level is global, it also affects any other code in the
+/// program which uses ffmpeg.
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL_SET.call_once(|| {});
+    unsafe {
+        av_log_set_level(level.as_av());
+    }
+}
+
+/// Silences libav logging to avoid spamming stderr unexpectedly, unless it has already been
+/// done or the user has chosen their own level, either with [`set_log_level`] or directly
+/// through ffmpeg.
+pub(crate) fn quiet_by_default() {
+    LOG_LEVEL_SET.call_once(|| unsafe {
+        // Any other level must have been chosen by someone.
+        if av_log_get_level() == AV_LOG_INFO as i32 {
+            av_log_set_level(AV_LOG_QUIET);
+        }
+    });
+}
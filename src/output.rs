@@ -1,59 +1,997 @@
 use std::{
+    collections::VecDeque,
     error::Error,
-    ffi::{CStr, CString},
+    ffi::{c_void, CStr, CString},
     path::Path,
     ptr::{self, NonNull},
+    thread,
+    time::{Duration, Instant},
 };
 
 use ffmpeg_sys_next::{
-    av_dict_free, av_dict_set, av_dict_set_int, av_interleaved_write_frame, av_packet_alloc,
-    av_packet_free, av_packet_rescale_ts, av_write_trailer, avcodec_alloc_context3,
-    avcodec_find_encoder, avcodec_free_context, avcodec_get_name, avcodec_open2,
+    av_dict_free, av_dict_get, av_dict_set, av_dict_set_int, av_dynarray_add_nofree, av_free,
+    av_interleaved_write_frame, av_log, av_mallocz, av_opt_set, av_opt_set_int, av_packet_alloc,
+    av_packet_free, av_packet_ref, av_packet_rescale_ts, av_write_trailer, avcodec_alloc_context3,
+    avcodec_find_encoder_by_name, avcodec_free_context, avcodec_open2,
     avcodec_parameters_from_context, avcodec_receive_packet, avcodec_send_frame,
-    avformat_alloc_output_context2, avformat_free_context, avformat_new_stream,
-    avformat_write_header, avio_closep, avio_open, sws_freeContext, sws_getContext, sws_scale,
-    AVCodec, AVCodecContext, AVCodecID, AVFormatContext, AVMediaType, AVPacket, AVPixelFormat,
-    AVStream, SwsContext, AVERROR, AVERROR_EOF, AVFMT_GLOBALHEADER, AVIO_FLAG_WRITE,
-    AV_CODEC_FLAG_GLOBAL_HEADER, EAGAIN, SWS_BICUBIC,
+    avformat_alloc_output_context2, avformat_free_context, avformat_network_init,
+    avformat_new_stream, avformat_write_header, avio_closep, avio_open, avio_seek,
+    sws_alloc_context, sws_freeContext, sws_freeFilter, sws_getCoefficients,
+    sws_getColorspaceDetails, sws_getContext, sws_getDefaultFilter, sws_init_context, sws_scale,
+    sws_setColorspaceDetails, AVChapter, AVCodec, AVCodecContext, AVColorPrimaries, AVColorSpace,
+    AVColorTransferCharacteristic, AVFormatContext, AVMediaType, AVPacket, AVPixelFormat,
+    AVRational, AVStream, SwsContext, SwsFilter, AVERROR, AVERROR_EOF, AVFMT_FLAG_BITEXACT,
+    AVFMT_GLOBALHEADER, AVIO_FLAG_WRITE, AVIO_SEEKABLE_NORMAL, AV_CODEC_FLAG_BITEXACT,
+    AV_CODEC_FLAG_GLOBAL_HEADER, AV_CODEC_FLAG_INTERLACED_DCT, AV_CODEC_FLAG_INTERLACED_ME,
+    AV_CODEC_FLAG_QSCALE, AV_DICT_IGNORE_SUFFIX, AV_LOG_WARNING, AV_NOPTS_VALUE, AV_PKT_FLAG_KEY,
+    EAGAIN, ENOMEM, ENOSPC, FF_QP2LAMBDA, SWS_ACCURATE_RND, SWS_BICUBIC, SWS_BITEXACT,
+    SWS_CS_BT2020,
 };
 
-use crate::{frame::Frame, make_av_error, OptionalSettings, X264Preset};
+use crate::{
+    frame::Frame, make_av_error, BitDepth, Callbacks, ChromaSubsampling, ContentLightLevel,
+    DitherMode, EffectiveSettings, EncodedPacket, EncoderError, FieldOrder, MasteringDisplay,
+    OptionalSettings, PacketStats, TeeOutput, TimingStats, VideoCodec, X264Preset,
+};
+
+/// Data attached to a single frame while it's encoded, without being kept on the frame.
+#[derive(Default)]
+pub(crate) struct FrameExtras<'a> {
+    pub sei: Option<&'a [u8]>,
+    pub quantizer_offset: Option<f32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    // no frames have been written yet
+    Initialized,
+    Writing,
+    // the encoder has been flushed, or at least tried to be, so nothing more can be written
+    Finished,
+}
 
 pub(crate) struct OutputStream {
-    filename: CString,
+    state: State,
 
-    stream: NonNull<AVStream>,
     codec: NonNull<AVCodec>,
     encoder_context: AVCodecContextWrapper,
 
     next_pts: i64,
+    // the PTS of the first frame
+    start_pts: i64,
+    // the number of frames given to write_frame, including skipped duplicates
+    frame_count: u64,
+    // if set, the PTS already on each frame is used instead of next_pts
+    use_frame_pts: bool,
+    // if set, keyframes are forced every this many seconds
+    keyframe_interval: Option<f64>,
+    forced_keyframes: u32,
+    field_order: Option<FieldOrder>,
+    hdr10: Option<(MasteringDisplay, ContentLightLevel)>,
+
+    callbacks: Callbacks,
+    #[cfg(feature = "cairo-input")]
+    burn_frame_number: bool,
 
     // used as temporary destination buffer for conversion when input frame has wrong pixel format
     temp_frame: Frame,
+    // a copy of the input frame to blur or draw on, so the caller's frame is left unchanged
+    scratch_frame: Option<Frame>,
     sws_context: Option<SwsContextWrapper>,
+    dither: Option<DitherMode>,
+    sharpen: Option<f32>,
+    denoise: Option<f32>,
+    bitexact_scaling: bool,
+
+    skip_duplicate_frames: bool,
+    // a copy of the last frame sent to the encoder, used to detect duplicates and to extend
+    // the final frame
+    last_frame: Option<Frame>,
+    skipped_duplicates: bool,
+    skip_bad_frames: bool,
+    // the number of frames the encoder rejected, if they're being skipped
+    dropped_frames: u64,
+    // how many frames long the final frame should be
+    final_frame_duration: u32,
+
+    realtime_pacing: bool,
+    // when the first frame was written and its PTS, if frames are being paced to real time
+    pacing_start: Option<(Instant, i64)>,
+
+    // how long each frame took to encode, if timing is being collected
+    frame_timings: Option<Vec<Duration>>,
 
     packet: AVPacketWrapper,
 
-    format_context: NonNull<AVFormatContext>,
+    // None if encoded packets are handed back to the caller instead of being written out
+    muxer: Option<Muxer>,
+    // further muxers the same packets are written to, with the settings each is opened with
+    extra_muxers: Vec<(Muxer, OptionalSettings)>,
+    encoded_packets: Vec<EncodedPacket>,
+    // the DTS of the last packet the encoder produced, in the encoder's time base
+    last_dts: Option<i64>,
+    // the total size of the packets the encoder produced
+    packet_bytes: u64,
+    // the PTS of every keyframe packet the encoder produced, in the encoder's time base
+    keyframe_indices: Vec<u64>,
+    // the PTS and index of each frame sent to the encoder whose packet hasn't come out yet
+    pending_frames: VecDeque<(i64, u64)>,
 }
 impl OutputStream {
-    pub fn new<P: AsRef<Path>>(
-        filename: P,
-        codec_id: AVCodecID,
+    /// If `filename` is `None`, nothing is written; encoded packets are instead collected to
+    /// be retrieved with [`Self::take_packets`].
+    pub fn new(
+        filename: Option<&Path>,
+        video_codec: VideoCodec,
         width: i32,
         height: i32,
-        framerate: i32,
+        framerate: (i32, i32),
         pixel_format: AVPixelFormat,
         settings: &OptionalSettings,
+    ) -> Result<Self, Box<dyn Error>> {
+        let encoder_name = video_codec.encoder_name();
+        let Some(codec) = NonNull::new(unsafe {
+            avcodec_find_encoder_by_name(encoder_name.as_ptr() as *const i8)
+        }) else {
+            let error_action = format!(
+                "Error finding encoder {}",
+                encoder_name.trim_end_matches('\0')
+            );
+            return Err(error_action.into());
+        };
+
+        if unsafe { codec.as_ref().type_ } != AVMediaType::AVMEDIA_TYPE_VIDEO {
+            return Err("Error: the specified codec is not a video codec".into());
+        }
+
+        // Opening the encoder would fail anyway, but with a much less helpful error. This
+        // mostly catches encoders built without 10-bit support.
+        if !supports_pixel_format(codec, pixel_format) {
+            return Err(format!(
+                "Error: encoder {} does not support the requested bit depth and chroma subsampling",
+                encoder_name.trim_end_matches('\0')
+            )
+            .into());
+        }
+
+        let muxer = filename
+            .map(|filename| Muxer::new(filename, framerate, settings))
+            .transpose()?;
+
+        let mut encoder_context = AVCodecContextWrapper::new(codec)?;
+
+        unsafe {
+            encoder_context.codec_context.as_mut().codec_id = codec.as_ref().id;
+            encoder_context.codec_context.as_mut().bit_rate = settings.bitrate.unwrap_or(800_000);
+            // Encoders which don't take the qp option directly use a fixed quantizer when the
+            // QSCALE flag is set, with global_quality giving it in lambda units.
+            if let Some(qp) = settings.qp {
+                encoder_context.codec_context.as_mut().flags |= AV_CODEC_FLAG_QSCALE as i32;
+                encoder_context.codec_context.as_mut().global_quality =
+                    qp as i32 * FF_QP2LAMBDA as i32;
+            }
+            encoder_context.codec_context.as_mut().width = width;
+            encoder_context.codec_context.as_mut().height = height;
+            // Each frame is one tick of the time base.
+            encoder_context.codec_context.as_mut().time_base.num = framerate.1;
+            encoder_context.codec_context.as_mut().time_base.den = framerate.0;
+            encoder_context.codec_context.as_mut().framerate.num = framerate.0;
+            encoder_context.codec_context.as_mut().framerate.den = framerate.1;
+            encoder_context.codec_context.as_mut().gop_size = settings.gop_frames(framerate);
+            // With only I-frames there's nothing for B-frames to refer to, and some encoders
+            // would otherwise still try to insert them.
+            if encoder_context.codec_context.as_ref().gop_size == 1 {
+                encoder_context.codec_context.as_mut().max_b_frames = 0;
+            }
+            if settings.fixed_gop {
+                encoder_context.codec_context.as_mut().keyint_min =
+                    encoder_context.codec_context.as_ref().gop_size;
+            } else if let Some(min_gop_size) = settings.min_gop_size {
+                // libx264 passes this on as min-keyint.
+                encoder_context.codec_context.as_mut().keyint_min = min_gop_size;
+            }
+            encoder_context.codec_context.as_mut().pix_fmt = pixel_format;
+            if let Some(ref_frames) = settings.ref_frames {
+                encoder_context.codec_context.as_mut().refs = ref_frames;
+            }
+            if let Some(max_qdiff) = settings.max_qdiff {
+                encoder_context.codec_context.as_mut().max_qdiff = max_qdiff;
+            }
+            if let Some(factor) = settings.b_quant_factor {
+                encoder_context.codec_context.as_mut().b_quant_factor = factor;
+            }
+            if let Some(bits) = settings.initial_buffer_occupancy {
+                encoder_context
+                    .codec_context
+                    .as_mut()
+                    .rc_initial_buffer_occupancy = bits as i32;
+            }
+            if let Some(field_order) = settings.field_order {
+                encoder_context.codec_context.as_mut().flags |=
+                    (AV_CODEC_FLAG_INTERLACED_DCT | AV_CODEC_FLAG_INTERLACED_ME) as i32;
+                encoder_context.codec_context.as_mut().field_order = field_order.as_av();
+            }
+            if settings.hdr10.is_some() {
+                let context = encoder_context.codec_context.as_mut();
+                context.color_primaries = AVColorPrimaries::AVCOL_PRI_BT2020;
+                context.color_trc = AVColorTransferCharacteristic::AVCOL_TRC_SMPTE2084;
+                context.colorspace = AVColorSpace::AVCOL_SPC_BT2020_NCL;
+            }
+            if let Some(location) = settings.chroma_location {
+                encoder_context
+                    .codec_context
+                    .as_mut()
+                    .chroma_sample_location = location.as_av();
+            }
+
+            if settings.deterministic {
+                // Encoding on one thread avoids output that depends on how the work was split.
+                encoder_context.codec_context.as_mut().thread_count = 1;
+                encoder_context.codec_context.as_mut().flags |= AV_CODEC_FLAG_BITEXACT as i32;
+            }
+
+            if muxer.as_ref().is_some_and(Muxer::needs_global_header) {
+                encoder_context.codec_context.as_mut().flags |= AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+            }
+        }
+
+        Ok(Self {
+            state: State::Initialized,
+            codec,
+            encoder_context,
+            next_pts: settings.start_pts.unwrap_or(0),
+            start_pts: settings.start_pts.unwrap_or(0),
+            frame_count: 0,
+            use_frame_pts: settings.use_frame_pts,
+            keyframe_interval: settings.keyframe_interval,
+            forced_keyframes: 0,
+            field_order: settings.field_order,
+            hdr10: settings.hdr10,
+            callbacks: Default::default(),
+            #[cfg(feature = "cairo-input")]
+            burn_frame_number: settings.burn_frame_number,
+            temp_frame: Frame::from_av_format(pixel_format, width, height)?,
+            scratch_frame: None,
+            sws_context: None,
+            dither: settings.gif_dither,
+            sharpen: settings.sws_sharpen,
+            denoise: settings.denoise,
+            bitexact_scaling: settings.deterministic,
+            skip_duplicate_frames: settings.skip_duplicate_frames,
+            last_frame: None,
+            skipped_duplicates: false,
+            skip_bad_frames: settings.skip_bad_frames,
+            dropped_frames: 0,
+            final_frame_duration: 1,
+            realtime_pacing: settings.realtime_pacing,
+            pacing_start: None,
+            frame_timings: settings.collect_timing.then(Vec::new),
+            packet: AVPacketWrapper::new()?,
+            muxer,
+            extra_muxers: Vec::new(),
+            encoded_packets: Vec::new(),
+            last_dts: None,
+            packet_bytes: 0,
+            keyframe_indices: Vec::new(),
+            pending_frames: VecDeque::new(),
+        })
+    }
+
+    pub fn open(&mut self, settings: &OptionalSettings) -> Result<(), Box<dyn Error>> {
+        let mut options = ptr::null_mut();
+        let video_codec = settings.codec.unwrap_or(VideoCodec::H264);
+
+        // x265 accepts the same preset names as x264.
+        if matches!(video_codec, VideoCodec::H264 | VideoCodec::H265) {
+            let preset = settings
+                .preset
+                .unwrap_or(X264Preset::Medium)
+                .as_bytes_with_nul();
+            unsafe {
+                av_dict_set(&mut options, "preset\0".as_ptr() as *const i8, preset, 0);
+            }
+        }
+
+        // libx264 only picks the High profiles automatically in some versions.
+        if video_codec == VideoCodec::H264 && settings.bit_depth == Some(BitDepth::Ten) {
+            let profile = settings
+                .chroma_subsampling
+                .unwrap_or(ChromaSubsampling::Yuv420)
+                .h264_high_bit_depth_profile();
+            unsafe {
+                av_dict_set(
+                    &mut options,
+                    "profile\0".as_ptr() as *const i8,
+                    profile.as_ptr() as *const i8,
+                    0,
+                );
+            }
+        }
+
+        if let Some(qp) = settings.qp {
+            unsafe {
+                av_dict_set_int(&mut options, "qp\0".as_ptr() as *const i8, qp as i64, 0);
+            }
+        } else if let Some(crf) = settings.crf {
+            unsafe {
+                av_dict_set_int(&mut options, "crf\0".as_ptr() as *const i8, crf, 0);
+            }
+
+            // libvpx only uses constant quality mode if there's no target bitrate.
+            if video_codec == VideoCodec::Vp9 {
+                unsafe {
+                    self.encoder_context.codec_context.as_mut().bit_rate = 0;
+                }
+            }
+        }
+
+        // Options for x264 and x265 that aren't exposed by ffmpeg directly, in the key=value
+        // syntax both encoders use.
+        let mut x26x_params = Vec::new();
+
+        // keyint_min is enough for libvpx, and libsvtav1 doesn't insert keyframes at scene cuts
+        // by default, but x264 and x265 need scene cut detection disabled explicitly: x264
+        // through ffmpeg's sc_threshold option, and x265 through its own parameters, since
+        // libx265 ignores sc_threshold.
+        if settings.fixed_gop {
+            match video_codec {
+                VideoCodec::H264 => unsafe {
+                    av_dict_set(
+                        &mut options,
+                        "sc_threshold\0".as_ptr() as *const i8,
+                        "0\0".as_ptr() as *const i8,
+                        0,
+                    );
+                },
+                VideoCodec::H265 => x26x_params.push("scenecut=0".to_owned()),
+                VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::Gif => {}
+            }
+        }
+
+        if let Some(closed) = settings.closed_gop {
+            let open_gop = if closed { "open-gop=0" } else { "open-gop=1" };
+            x26x_params.push(open_gop.to_owned());
+        }
+
+        // x265 only writes the HDR10 SEI messages if it's given the metadata up front.
+        if let (Some((mastering, light_level)), VideoCodec::H265) = (settings.hdr10, video_codec) {
+            let chromaticity = |(x, y): (f64, f64)| {
+                format!("({},{})", (x * 50000.0).round(), (y * 50000.0).round())
+            };
+            x26x_params.push("hdr10=1".to_owned());
+            x26x_params.push(format!(
+                "master-display=G{}B{}R{}WP{}L({},{})",
+                chromaticity(mastering.green),
+                chromaticity(mastering.blue),
+                chromaticity(mastering.red),
+                chromaticity(mastering.white_point),
+                (mastering.max_luminance * 10000.0).round(),
+                (mastering.min_luminance * 10000.0).round(),
+            ));
+            x26x_params.push(format!(
+                "max-cll={},{}",
+                light_level.max_cll, light_level.max_fall
+            ));
+        }
+
+        // These are only supported with x264, which validation makes sure of.
+        if let Some(method) = settings.motion_estimation {
+            x26x_params.push(format!("me={}", method.as_str()));
+        }
+        if let Some(range) = settings.me_range {
+            x26x_params.push(format!("merange={}", range));
+        }
+        if let Some(trellis) = settings.trellis {
+            x26x_params.push(format!("trellis={}", trellis));
+        }
+        if let Some((top, bottom, left, right)) = settings.display_crop {
+            x26x_params.push(format!("crop-rect={},{},{},{}", left, top, right, bottom));
+        }
+
+        if settings.disable_psy {
+            // x265 splits its psychovisual optimizations into two separate options.
+            match video_codec {
+                VideoCodec::H265 => {
+                    x26x_params.push("psy-rd=0".to_owned());
+                    x26x_params.push("psy-rdoq=0".to_owned());
+                }
+                _ => x26x_params.push("psy=0".to_owned()),
+            }
+        }
+
+        // x264 picks its assembly routines based on the CPU, and they don't all produce
+        // exactly the same output.
+        if settings.deterministic && video_codec == VideoCodec::H264 {
+            x26x_params.push("asm=0".to_owned());
+        }
+
+        let params_key = match video_codec {
+            VideoCodec::H264 => Some("x264-params\0"),
+            VideoCodec::H265 => Some("x265-params\0"),
+            VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::Gif => None,
+        };
+        if let Some(key) = params_key.filter(|_| !x26x_params.is_empty()) {
+            let params = CString::new(x26x_params.join(":"))?;
+            unsafe {
+                av_dict_set(&mut options, key.as_ptr() as *const i8, params.as_ptr(), 0);
+            }
+        }
+
+        if let Some(film_grain) = settings.film_grain {
+            let params = CString::new(format!(
+                "film-grain={}:film-grain-denoise={}",
+                film_grain.strength, film_grain.denoise as u8
+            ))?;
+            unsafe {
+                av_dict_set(
+                    &mut options,
+                    "svtav1-params\0".as_ptr() as *const i8,
+                    params.as_ptr(),
+                    0,
+                );
+            }
+        }
+
+        let result = unsafe {
+            avcodec_open2(
+                self.encoder_context.codec_context.as_ptr(),
+                self.codec.as_ptr(),
+                &mut options,
+            )
+        };
+        unsafe { av_dict_free(&mut options) };
+        if result < 0 {
+            return Err(make_av_error("opening video codec", result));
+        }
+
+        if let Some(muxer) = &mut self.muxer {
+            muxer.open(&self.encoder_context, settings)?;
+        }
+        for (muxer, settings) in &mut self.extra_muxers {
+            muxer.open(&self.encoder_context, settings)?;
+        }
+        Ok(())
+    }
+
+    /// Adds another output the encoded packets are written to. This must be called before
+    /// [`Self::open`]. Apart from the container format and muxer options, the output uses the
+    /// same settings as the rest of the stream.
+    pub fn add_output(
+        &mut self,
+        output: &TeeOutput,
+        settings: &OptionalSettings,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut settings = settings.clone();
+        settings.format_name = output.format_name.clone();
+        settings.streaming = output.streaming;
+        settings.muxer_options = output.muxer_options.clone();
+        settings.prepare_output_dir(&output.target)?;
+
+        let framerate = unsafe { self.encoder_context.codec_context.as_ref().framerate };
+        let muxer = Muxer::new(&output.target, (framerate.num, framerate.den), &settings)?;
+        // The headers are then only in the extradata, but muxers which want them in the
+        // stream, like MPEG-TS, insert the extradata before keyframes themselves.
+        if muxer.needs_global_header() {
+            unsafe {
+                self.encoder_context.codec_context.as_mut().flags |=
+                    AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+            }
+        }
+
+        self.extra_muxers.push((muxer, settings));
+        Ok(())
+    }
+
+    pub fn encoder_delay(&self) -> i64 {
+        unsafe { self.encoder_context.codec_context.as_ref().delay as i64 }
+    }
+
+    pub fn effective_settings(&self) -> EffectiveSettings {
+        EffectiveSettings::from_context(unsafe { self.encoder_context.codec_context.as_ref() })
+    }
+
+    pub fn set_callbacks(&mut self, callbacks: Callbacks) {
+        self.callbacks = callbacks;
+    }
+
+    pub fn take_callbacks(&mut self) -> Callbacks {
+        std::mem::take(&mut self.callbacks)
+    }
+
+    pub fn write_frame(
+        &mut self,
+        frame: &mut Frame,
+        extras: FrameExtras,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.state == State::Finished {
+            return Err(EncoderError::InvalidState.into());
+        }
+        let first_frame = self.state == State::Initialized;
+        // counted before this frame is
+        let frame_index = self.frame_count;
+
+        let pts = if self.use_frame_pts {
+            let pts = frame
+                .pts()
+                .ok_or("Error: use_frame_pts is enabled, but the frame has no PTS")?;
+            if first_frame && pts < self.start_pts {
+                return Err(format!(
+                    "Error: frame PTS {} is before the start PTS {}",
+                    pts, self.start_pts
+                )
+                .into());
+            }
+            // next_pts is one past the previous frame's PTS, so this also rejects repeats.
+            if pts < self.next_pts {
+                return Err(format!(
+                    "Error: frame PTS {} is not after the previous frame's PTS {}",
+                    pts,
+                    self.next_pts - 1
+                )
+                .into());
+            }
+            pts
+        } else {
+            self.next_pts
+        };
+
+        if self.realtime_pacing {
+            self.wait_until_due(pts);
+        }
+
+        if let Some(hook) = &self.callbacks.pre_encode {
+            // If the hook panicked before, it may have been left half way through changing
+            // its own state, so it can't safely be called again.
+            let mut hook = hook
+                .lock()
+                .map_err(|_| "Error: the pre_encode hook panicked on an earlier frame")?;
+            // The same number burn_frame_number draws, unless timestamps are given.
+            let index = if self.use_frame_pts {
+                pts as u64
+            } else {
+                frame_index
+            };
+            (*hook)(frame, index);
+        }
+
+        #[cfg(feature = "cairo-input")]
+        let burn_frame_number = self.burn_frame_number;
+        #[cfg(not(feature = "cairo-input"))]
+        let burn_frame_number = false;
+        let frame = if self.denoise.is_some() || burn_frame_number {
+            let scratch = copy_to_scratch(&mut self.scratch_frame, frame)?;
+            // Blurring first keeps the burned-in frame number sharp.
+            if let Some(strength) = self.denoise {
+                scratch.blur(strength)?;
+            }
+            #[cfg(feature = "cairo-input")]
+            if burn_frame_number {
+                scratch.burn_in_text(&frame_index.to_string())?;
+            }
+            scratch
+        } else {
+            frame
+        };
+
+        let frame_to_send = if unsafe { self.encoder_context.codec_context.as_ref().pix_fmt as i32 }
+            != frame.pixel_format()
+            || frame.width() != self.temp_frame.width()
+            || frame.height() != self.temp_frame.height()
+        {
+            // The input frame may have been resized since the last call, in which case the
+            // existing context can't be reused.
+            if !self
+                .sws_context
+                .as_ref()
+                .is_some_and(|sws_context| sws_context.accepts(frame))
+            {
+                let sws_context = SwsContextWrapper::new(
+                    frame,
+                    &self.temp_frame,
+                    self.dither,
+                    self.sharpen,
+                    self.bitexact_scaling,
+                )?;
+                // The video is tagged as BT.2020, so its YUV values have to match.
+                if self.hdr10.is_some() {
+                    sws_context.use_bt2020_matrix()?;
+                }
+                self.sws_context = Some(sws_context);
+            }
+            self.sws_context
+                .as_ref()
+                .unwrap()
+                .scale(frame, &mut self.temp_frame)?;
+
+            &mut self.temp_frame
+        } else {
+            frame
+        };
+
+        // A frame carrying SEI data can't be dropped, even if its picture is a duplicate, but
+        // it still becomes the frame the following ones are compared against.
+        if self.skip_duplicate_frames
+            && extras.sei.is_none()
+            && self
+                .last_frame
+                .as_ref()
+                .is_some_and(|last_frame| last_frame.same_contents(frame_to_send))
+        {
+            // Leaving a gap in the timestamps makes the previous frame last longer.
+            self.next_pts = pts.checked_add(1).ok_or(EncoderError::TimestampOverflow)?;
+            self.frame_count += 1;
+            self.skipped_duplicates = true;
+            return Ok(());
+        }
+
+        frame_to_send.set_pts(pts);
+        // Players can't start decoding until they see a keyframe, so make sure there's one
+        // at the very start. Unless a keyframe interval was given, the encoder decides where
+        // to put all the others.
+        let keyframe = match self.keyframe_interval {
+            Some(interval) => {
+                let time_base = unsafe { self.encoder_context.codec_context.as_ref().time_base };
+                let time =
+                    (pts - self.start_pts) as f64 * time_base.num as f64 / time_base.den as f64;
+                // Comparing against the number of keyframes so far, rather than the time of
+                // the last one, keeps rounding errors from accumulating.
+                time >= self.forced_keyframes as f64 * interval
+            }
+            None => first_frame,
+        };
+        frame_to_send.set_keyframe(keyframe);
+        frame_to_send.set_field_order(self.field_order);
+        frame_to_send.set_sei_unregistered(extras.sei)?;
+        frame_to_send.set_quantizer_offset(extras.quantizer_offset)?;
+        frame_to_send.set_hdr10_metadata(self.hdr10)?;
+        self.next_pts = pts.checked_add(1).ok_or(EncoderError::TimestampOverflow)?;
+
+        let start = self.frame_timings.is_some().then(Instant::now);
+
+        let result = self.encoder_context.try_send_frame(frame_to_send);
+        // The encoder keeps its own reference to the side data, and the frame may be reused.
+        frame_to_send.set_sei_unregistered(None)?;
+        frame_to_send.set_quantizer_offset(None)?;
+        frame_to_send.set_hdr10_metadata(None)?;
+        if result < 0 {
+            // Running out of memory or having already been flushed isn't about this frame,
+            // so the following frames would fail too.
+            if !self.skip_bad_frames || result == AVERROR(ENOMEM) || result == AVERROR_EOF {
+                return Err(make_av_error("sending frame to encoder", result));
+            }
+            // Like a skipped duplicate, the gap left in the timestamps makes the previous
+            // frame last longer.
+            self.dropped_frames += 1;
+            self.encoder_context.log_warning(&format!(
+                "Skipping frame {} after error: {}",
+                pts,
+                make_av_error("sending frame to encoder", result)
+            ));
+            return Ok(());
+        }
+        // Only a frame the encoder accepted counts, so a dropped one leaves the next frame to
+        // take its place.
+        self.state = State::Writing;
+        self.frame_count += 1;
+        if keyframe && self.keyframe_interval.is_some() {
+            self.forced_keyframes += 1;
+        }
+        if self.skip_duplicate_frames || self.final_frame_duration > 1 {
+            if let Some(last_frame) = &mut self.last_frame {
+                last_frame.copy_from(frame_to_send)?;
+            } else {
+                let mut last_frame = Frame::from_av_format(
+                    frame_to_send.av_pixel_format(),
+                    frame_to_send.width(),
+                    frame_to_send.height(),
+                )?;
+                last_frame.copy_from(frame_to_send)?;
+                self.last_frame = Some(last_frame);
+            }
+            self.skipped_duplicates = false;
+        }
+        self.pending_frames.push_back((pts, frame_index));
+
+        self.flush()?;
+
+        if let (Some(frame_timings), Some(start)) = (&mut self.frame_timings, start) {
+            frame_timings.push(start.elapsed());
+        }
+        Ok(())
+    }
+
+    /// Sleeps until the frame at `pts` should be shown, counting from the first paced frame.
+    fn wait_until_due(&mut self, pts: i64) {
+        let now = Instant::now();
+        let (start, start_pts) = *self.pacing_start.get_or_insert((now, pts));
+
+        let time_base = unsafe { self.encoder_context.codec_context.as_ref().time_base };
+        let seconds = (pts - start_pts) as f64 * time_base.num as f64 / time_base.den as f64;
+        let due = start + Duration::from_secs_f64(seconds.max(0.0));
+        if due > now {
+            thread::sleep(due - now);
+        }
+    }
+
+    /// The framerate the output ended up with. Without a muxer, this is the encoder's.
+    pub fn effective_framerate(&self) -> (i32, i32) {
+        self.muxer
+            .as_ref()
+            .and_then(Muxer::frame_rate)
+            .unwrap_or_else(|| {
+                let framerate = unsafe { self.encoder_context.codec_context.as_ref().framerate };
+                (framerate.num, framerate.den)
+            })
+    }
+
+    /// Whether the output can be seeked. Always false if there is no muxer.
+    pub fn is_seekable(&self) -> bool {
+        self.muxer.as_ref().is_some_and(Muxer::is_seekable)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    #[cfg(feature = "serde")]
+    /// The length of the video so far, up to the end of the last frame.
+    pub fn duration_seconds(&self) -> f64 {
+        let time_base = unsafe { self.encoder_context.codec_context.as_ref().time_base };
+        (self.next_pts - self.start_pts) as f64 * time_base.num as f64 / time_base.den as f64
+    }
+
+    /// The size of the finished output. Always `None` if there is no muxer.
+    #[cfg(feature = "serde")]
+    pub fn output_size(&self) -> Option<u64> {
+        self.muxer.as_ref().and_then(Muxer::output_size)
+    }
+
+    /// The number of bytes written to the output so far. Without a muxer, or if the output
+    /// doesn't know its position, this is the total size of the packets produced instead.
+    pub fn bytes_written(&self) -> u64 {
+        self.muxer
+            .as_ref()
+            .and_then(Muxer::bytes_written)
+            .unwrap_or(self.packet_bytes)
+    }
+
+    /// Only takes effect if set before the final frame is written, since the previous frame
+    /// isn't kept otherwise.
+    pub fn set_final_frame_duration(&mut self, frames: u32) {
+        self.final_frame_duration = frames;
+    }
+
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+
+    pub fn timing_stats(&self) -> Option<TimingStats> {
+        TimingStats::from_durations(self.frame_timings.as_deref()?)
+    }
+
+    pub fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.state == State::Finished {
+            return Err(EncoderError::InvalidState.into());
+        }
+        // Even if finishing fails part way, the encoder can't be used again.
+        self.state = State::Finished;
+
+        // If the video ends with skipped duplicates, or the final frame should be shown for
+        // longer than usual, nothing after the last encoded frame marks how long it should be
+        // shown for, so repeat it once at the very end.
+        let extra_frames = i64::from(self.final_frame_duration.saturating_sub(1));
+        if let Some(last_frame) = self
+            .last_frame
+            .as_mut()
+            .filter(|_| self.skipped_duplicates || extra_frames > 0)
+        {
+            let pts = (self.next_pts - 1)
+                .checked_add(extra_frames)
+                .ok_or(EncoderError::TimestampOverflow)?;
+            self.next_pts = pts + 1;
+            last_frame.set_pts(pts);
+            last_frame.set_keyframe(false);
+            self.encoder_context.send_frame(last_frame)?;
+            // It's a repeat of the final frame, so its packet belongs to that frame.
+            self.pending_frames
+                .push_back((pts, self.frame_count.saturating_sub(1)));
+            self.flush()?;
+        }
+
+        self.encoder_context.finish()?;
+        self.flush()?;
+
+        // Every output gets a trailer, even if an earlier one couldn't be finished.
+        let mut result = match &mut self.muxer {
+            Some(muxer) => muxer.write_trailer(),
+            None => Ok(()),
+        };
+        for (muxer, _) in &mut self.extra_muxers {
+            let trailer_result = muxer.write_trailer();
+            if result.is_ok() {
+                result = trailer_result;
+            }
+        }
+        result
+    }
+
+    pub fn keyframe_indices(&self) -> &[u64] {
+        &self.keyframe_indices
+    }
+
+    /// Removes and returns the packets collected so far, if there is no muxer.
+    pub fn take_packets(&mut self) -> Vec<EncodedPacket> {
+        std::mem::take(&mut self.encoded_packets)
+    }
+
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        let time_base = unsafe { self.encoder_context.codec_context.as_ref().time_base };
+        let muxer = &self.muxer;
+        let extra_muxers = &self.extra_muxers;
+        let encoded_packets = &mut self.encoded_packets;
+        let last_dts = &mut self.last_dts;
+        let packet_bytes = &mut self.packet_bytes;
+        let keyframe_indices = &mut self.keyframe_indices;
+        let pending_frames = &mut self.pending_frames;
+        let use_frame_pts = self.use_frame_pts;
+        let on_packet = &self.callbacks.on_packet;
+
+        self.encoder_context.flush(&mut self.packet, |packet| {
+            // Muxers only warn about bad timestamps, if they notice at all, so catch them here
+            // where the cause is easier to find.
+            let (pts, dts) = unsafe { (packet.packet.as_ref().pts, packet.packet.as_ref().dts) };
+            check_packet_timestamps(pts, dts, last_dts)?;
+
+            *packet_bytes += unsafe { packet.packet.as_ref().size } as u64;
+
+            // Packets come out in decoding order, and every frame's DTS is at most its PTS, so
+            // frames shown before this packet's DTS have already had their packets. Dropping
+            // them keeps frames an encoder never produced a packet for from piling up.
+            if dts != AV_NOPTS_VALUE {
+                pending_frames.retain(|&(frame_pts, _)| frame_pts >= dts);
+            }
+            let frame_index = pending_frames
+                .iter()
+                .position(|&(frame_pts, _)| frame_pts == pts)
+                .and_then(|position| pending_frames.remove(position))
+                .map(|(_, index)| index);
+
+            if unsafe { packet.packet.as_ref().flags } & AV_PKT_FLAG_KEY as i32 != 0 {
+                keyframe_indices.push(if use_frame_pts {
+                    pts.max(0) as u64
+                } else {
+                    frame_index.unwrap_or_default()
+                });
+            }
+
+            if let Some(on_packet) = on_packet {
+                let mut on_packet = on_packet
+                    .lock()
+                    .map_err(|_| "Error: the on_packet callback panicked on an earlier packet")?;
+                (*on_packet)(&PacketStats::from_av(
+                    unsafe { packet.packet.as_ref() },
+                    time_base,
+                    // Every packet comes from a frame that was sent, so this is always found.
+                    frame_index.unwrap_or_default(),
+                ));
+            }
+
+            // Writing a packet takes it over, so each extra muxer is given its own reference.
+            for (extra_muxer, _) in extra_muxers {
+                extra_muxer.write_packet(&mut packet.new_ref()?, time_base)?;
+            }
+
+            match muxer {
+                Some(muxer) => muxer.write_packet(packet, time_base),
+                None => {
+                    encoded_packets.push(EncodedPacket::from_av(unsafe { packet.packet.as_ref() }));
+                    Ok(())
+                }
+            }
+        })
+    }
+}
+// Safety: none of the ffmpeg contexts are shared with anything outside this object, and they
+// are only ever used from one thread at a time.
+unsafe impl Send for OutputStream {}
+
+/// Checks that a packet's DTS comes after the previous packet's, and isn't after its own PTS,
+/// and then records it as the previous DTS. Packets without a DTS aren't checked.
+fn check_packet_timestamps(
+    pts: i64,
+    dts: i64,
+    last_dts: &mut Option<i64>,
+) -> Result<(), Box<dyn Error>> {
+    if dts == AV_NOPTS_VALUE {
+        return Ok(());
+    }
+    if let Some(last_dts) = *last_dts {
+        if dts <= last_dts {
+            return Err(format!(
+                "Error: encoder produced non-monotonic DTS {} after {}",
+                dts, last_dts
+            )
+            .into());
+        }
+    }
+    if pts != AV_NOPTS_VALUE && dts > pts {
+        return Err(format!("Error: encoder produced DTS {} after PTS {}", dts, pts).into());
+    }
+    *last_dts = Some(dts);
+    Ok(())
+}
+
+/// Copies `frame` into `scratch`, reallocating it if its format or size doesn't match, and
+/// returns the copy.
+fn copy_to_scratch<'a>(
+    scratch: &'a mut Option<Frame>,
+    frame: &Frame,
+) -> Result<&'a mut Frame, Box<dyn Error>> {
+    let matches = scratch.as_ref().is_some_and(|scratch| {
+        scratch.pixel_format() == frame.pixel_format()
+            && scratch.width() == frame.width()
+            && scratch.height() == frame.height()
+    });
+    if !matches {
+        *scratch = Some(Frame::from_av_format(
+            frame.av_pixel_format(),
+            frame.width(),
+            frame.height(),
+        )?);
+    }
+
+    let scratch = scratch.as_mut().unwrap();
+    scratch.copy_from(frame)?;
+    Ok(scratch)
+}
+
+/// Whether the encoder accepts frames in the given pixel format. Encoders that don't list
+/// their formats are assumed to accept anything.
+fn supports_pixel_format(codec: NonNull<AVCodec>, pixel_format: AVPixelFormat) -> bool {
+    let mut format = unsafe { codec.as_ref().pix_fmts };
+    if format.is_null() {
+        return true;
+    }
+
+    unsafe {
+        // The list is terminated by AV_PIX_FMT_NONE.
+        while *format != AVPixelFormat::AV_PIX_FMT_NONE {
+            if *format == pixel_format {
+                return true;
+            }
+            format = format.add(1);
+        }
+    }
+    false
+}
+
+/// Writes encoded packets into the output file.
+struct Muxer {
+    filename: CString,
+
+    stream: NonNull<AVStream>,
+    format_context: NonNull<AVFormatContext>,
+}
+impl Muxer {
+    fn new(
+        filename: &Path,
+        framerate: (i32, i32),
+        settings: &OptionalSettings,
     ) -> Result<Self, Box<dyn Error>> {
         let filename = CString::new(
             filename
-                .as_ref()
                 .to_str()
                 .ok_or("Filename is invalid UTF-8")?
                 .as_bytes(),
         )?;
 
+        let format_name = settings
+            .format_name
+            .as_deref()
+            .map(CString::new)
+            .transpose()?;
+
         let format_context = {
             let mut context = ptr::null_mut();
 
@@ -61,7 +999,9 @@ impl OutputStream {
                 avformat_alloc_output_context2(
                     &mut context,
                     ptr::null_mut(),
-                    ptr::null_mut(),
+                    format_name
+                        .as_ref()
+                        .map_or(ptr::null(), |format_name| format_name.as_ptr()),
                     filename.as_bytes_with_nul().as_ptr() as *mut i8,
                 )
             };
@@ -79,98 +1019,164 @@ impl OutputStream {
             context
         };
 
-        let Some(codec) = NonNull::new(unsafe { avcodec_find_encoder(codec_id) }) else {
-            let name = unsafe { avcodec_get_name(codec_id) };
-            let error_action = format!(
-                "Error finding encoder for codec {}",
-                unsafe { CStr::from_ptr(name) }
-                    .to_str()
-                    .expect("avcodec_get_name returned invalid UTF-8")
-            );
-            return Err(error_action.into());
-        };
-
-        if unsafe { codec.as_ref().type_ } != AVMediaType::AVMEDIA_TYPE_VIDEO {
-            return Err("Error: the specified codec is not a video codec".into());
-        }
-
         let Some(mut stream) = NonNull::new(unsafe { avformat_new_stream(format_context.as_ptr(), ptr::null_mut()) }) else {
             return Err("Error allocating AVStream".into());
         };
-        unsafe {
-            stream.as_mut().id = (format_context.as_ref().nb_streams - 1) as i32;
-        }
-
-        let mut encoder_context = AVCodecContextWrapper::new(codec)?;
-
-        unsafe {
-            encoder_context.codec_context.as_mut().codec_id = codec.as_ref().id;
-            encoder_context.codec_context.as_mut().bit_rate = settings.bitrate.unwrap_or(800_000);
-            encoder_context.codec_context.as_mut().width = width;
-            encoder_context.codec_context.as_mut().height = height;
-            stream.as_mut().time_base.num = 1;
-            stream.as_mut().time_base.den = framerate;
-            encoder_context.codec_context.as_mut().time_base = stream.as_ref().time_base;
-            encoder_context.codec_context.as_mut().gop_size = settings.gop_size.unwrap_or(10);
-            encoder_context.codec_context.as_mut().pix_fmt = pixel_format;
-
-            if format_context.as_ref().flags & AVFMT_GLOBALHEADER != 0 {
-                encoder_context.codec_context.as_mut().flags |= AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+        unsafe {
+            stream.as_mut().id = (format_context.as_ref().nb_streams - 1) as i32;
+            stream.as_mut().time_base.num = framerate.1;
+            stream.as_mut().time_base.den = framerate.0;
+            // With frame timestamps, the framerate only sets their precision.
+            if !settings.use_frame_pts {
+                stream.as_mut().avg_frame_rate.num = framerate.0;
+                stream.as_mut().avg_frame_rate.den = framerate.1;
             }
         }
 
         Ok(Self {
             filename,
             stream,
-            codec,
-            encoder_context,
-            next_pts: 0,
-            temp_frame: Frame::new(pixel_format, width, height)?,
-            sws_context: None,
-            packet: AVPacketWrapper::new()?,
             format_context,
         })
     }
 
-    pub fn open(&mut self, settings: &OptionalSettings) -> Result<(), Box<dyn Error>> {
-        let mut options = ptr::null_mut();
+    /// Whether the container wants codec headers stored once in the stream parameters,
+    /// rather than repeated in the stream itself.
+    fn needs_global_header(&self) -> bool {
+        // This is a property of the container format, not of this particular file.
+        unsafe { (*self.format_context.as_ref().oformat).flags & AVFMT_GLOBALHEADER != 0 }
+    }
+
+    /// The stream's framerate, as the muxer left it after writing the header. This is its
+    /// `r_frame_rate` if the muxer set one, and otherwise the inverse of the stream's time base,
+    /// which the muxer may have changed from the one requested.
+    fn frame_rate(&self) -> Option<(i32, i32)> {
+        let stream = unsafe { self.stream.as_ref() };
+        let rate = stream.r_frame_rate;
+        if rate.num > 0 && rate.den > 0 {
+            return Some((rate.num, rate.den));
+        }
+        let time_base = stream.time_base;
+        (time_base.num > 0 && time_base.den > 0).then_some((time_base.den, time_base.num))
+    }
 
-        let preset = settings
-            .preset
-            .unwrap_or(X264Preset::Medium)
-            .as_bytes_with_nul();
+    /// Whether the output can be seeked, which is only known once it has been opened.
+    fn is_seekable(&self) -> bool {
         unsafe {
-            av_dict_set(&mut options, "preset\0".as_ptr() as *const i8, preset, 0);
+            let pb = self.format_context.as_ref().pb;
+            !pb.is_null() && (*pb).seekable & AVIO_SEEKABLE_NORMAL as i32 != 0
         }
+    }
 
-        if let Some(crf) = settings.crf {
-            unsafe {
-                av_dict_set_int(&mut options, "crf\0".as_ptr() as *const i8, crf, 0);
-            }
+    /// The current position in the output, which is the number of bytes written to it, as
+    /// long as nothing has seeked back yet.
+    fn bytes_written(&self) -> Option<u64> {
+        let pb = unsafe { self.format_context.as_ref().pb };
+        if pb.is_null() {
+            return None;
         }
+        // This is what avio_tell does, which is an inline function and so isn't exported.
+        const SEEK_CUR: i32 = 1;
+        u64::try_from(unsafe { avio_seek(pb, 0, SEEK_CUR) }).ok()
+    }
 
-        let result = unsafe {
-            avcodec_open2(
-                self.encoder_context.codec_context.as_ptr(),
-                self.codec.as_ptr(),
-                &mut options,
-            )
-        };
-        unsafe { av_dict_free(&mut options) };
-        if result < 0 {
-            return Err(make_av_error("opening video codec", result));
+    #[cfg(feature = "serde")]
+    fn output_size(&self) -> Option<u64> {
+        use ffmpeg_sys_next::avio_size;
+
+        let pb = unsafe { self.format_context.as_ref().pb };
+        if pb.is_null() {
+            return None;
         }
+        // Outputs that can't be seeked, like pipes, don't know their size, but everything
+        // has still been written before the current position.
+        match unsafe { avio_size(pb) } {
+            size if size >= 0 => Some(size as u64),
+            _ => self.bytes_written(),
+        }
+    }
+
+    /// Whether the muxer will need to seek back to finish the file. MP4-style containers
+    /// write their index after all the packets and then go back to fix up the header,
+    /// unless they're writing fragments.
+    fn requires_seeking(&self, settings: &OptionalSettings) -> bool {
+        let fragmented = settings
+            .muxer_options
+            .iter()
+            .filter(|(key, _)| key == "movflags")
+            .any(|(_, value)| value.contains("frag_") || value.contains("empty_moov"));
+
+        self.is_mov_family() && !fragmented
+    }
+
+    fn format_name(&self) -> &CStr {
+        unsafe { CStr::from_ptr((*self.format_context.as_ref().oformat).name) }
+    }
+
+    /// Whether the container is MP4, QuickTime, or one of their close relatives, which are
+    /// all written by the same muxer.
+    fn is_mov_family(&self) -> bool {
+        matches!(
+            self.format_name().to_bytes(),
+            b"mp4" | b"mov" | b"3gp" | b"3g2" | b"psp" | b"ipod" | b"f4v"
+        )
+    }
 
+    fn open(
+        &mut self,
+        encoder_context: &AVCodecContextWrapper,
+        settings: &OptionalSettings,
+    ) -> Result<(), Box<dyn Error>> {
         let result = unsafe {
             avcodec_parameters_from_context(
                 self.stream.as_ref().codecpar,
-                self.encoder_context.codec_context.as_ptr(),
+                encoder_context.codec_context.as_ptr(),
             )
         };
         if result < 0 {
             return Err(make_av_error("copying stream parameters", result));
         }
 
+        // Containers that want a global header need the codec's extradata (for H.264, the
+        // SPS and PPS) in the stream parameters, or the file won't be playable.
+        let needs_extradata = unsafe {
+            encoder_context.codec_context.as_ref().flags & AV_CODEC_FLAG_GLOBAL_HEADER as i32 != 0
+        };
+        let has_extradata = unsafe {
+            let codecpar = self.stream.as_ref().codecpar;
+            !(*codecpar).extradata.is_null() && (*codecpar).extradata_size > 0
+        };
+        if needs_extradata && !has_extradata {
+            return Err(
+                "Error: the encoder did not produce the global header required by the container"
+                    .into(),
+            );
+        }
+
+        // Checked before opening the file, so an unsupported container doesn't leave an empty
+        // file behind.
+        if settings.mp4_brand.is_some() && !self.is_mov_family() {
+            return Err(format!(
+                "Error: the {} container does not have a brand",
+                self.format_name().to_string_lossy()
+            )
+            .into());
+        }
+        // Only Matroska has somewhere to put VP9's separately coded alpha channel; other
+        // muxers silently drop it.
+        if settings.alpha && !matches!(self.format_name().to_bytes(), b"matroska" | b"webm") {
+            return Err("Error: alpha is only kept in WebM and Matroska output".into());
+        }
+        self.add_chapters(settings)?;
+        #[cfg(feature = "image-input")]
+        let cover_stream_index = self.add_cover_stream(settings)?;
+
+        if settings.streaming {
+            unsafe {
+                avformat_network_init();
+            }
+        }
+
         let result = unsafe {
             avio_open(
                 &mut self.format_context.as_mut().pb,
@@ -183,20 +1189,233 @@ impl OutputStream {
             return Err(make_av_error("opening destination file", result));
         }
 
-        self.write_header()
+        self.write_header(settings)?;
+
+        #[cfg(feature = "image-input")]
+        if let (Some(stream_index), Some(image)) = (cover_stream_index, &settings.cover_image) {
+            self.write_cover(stream_index, image)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a stream for the cover image, if there is one, returning its index.
+    #[cfg(feature = "image-input")]
+    fn add_cover_stream(
+        &mut self,
+        settings: &OptionalSettings,
+    ) -> Result<Option<i32>, Box<dyn Error>> {
+        use ffmpeg_sys_next::{AVCodecID, AV_DISPOSITION_ATTACHED_PIC};
+
+        let Some(image) = &settings.cover_image else {
+            return Ok(None);
+        };
+        if !self.is_mov_family() {
+            return Err(format!(
+                "Error: cover images are not supported in the {} container",
+                self.format_name().to_string_lossy()
+            )
+            .into());
+        }
+
+        let Some(mut stream) = NonNull::new(unsafe {
+            avformat_new_stream(self.format_context.as_ptr(), ptr::null_mut())
+        }) else {
+            return Err("Error allocating AVStream".into());
+        };
+        unsafe {
+            stream.as_mut().id = (self.format_context.as_ref().nb_streams - 1) as i32;
+            // An attached picture is a single image rather than a video, and players show it
+            // as the file's thumbnail.
+            stream.as_mut().disposition |= AV_DISPOSITION_ATTACHED_PIC as i32;
+
+            let codecpar = stream.as_ref().codecpar;
+            (*codecpar).codec_type = AVMediaType::AVMEDIA_TYPE_VIDEO;
+            (*codecpar).codec_id = AVCodecID::AV_CODEC_ID_PNG;
+            (*codecpar).width = image.width() as i32;
+            (*codecpar).height = image.height() as i32;
+
+            Ok(Some(stream.as_ref().index))
+        }
+    }
+
+    /// Writes the cover image as the only packet of its stream.
+    #[cfg(feature = "image-input")]
+    fn write_cover(
+        &mut self,
+        stream_index: i32,
+        image: &image::RgbImage,
+    ) -> Result<(), Box<dyn Error>> {
+        use ffmpeg_sys_next::av_new_packet;
+        use image::ImageEncoder;
+
+        let mut png = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut png).write_image(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            image::ColorType::Rgb8,
+        )?;
+
+        let packet = AVPacketWrapper::new()?;
+        let result = unsafe { av_new_packet(packet.packet.as_ptr(), png.len() as i32) };
+        if result < 0 {
+            return Err(make_av_error("allocating cover image packet", result));
+        }
+        unsafe {
+            ptr::copy_nonoverlapping(png.as_ptr(), (*packet.packet.as_ptr()).data, png.len());
+            (*packet.packet.as_ptr()).stream_index = stream_index;
+            (*packet.packet.as_ptr()).flags |= AV_PKT_FLAG_KEY as i32;
+            (*packet.packet.as_ptr()).pts = 0;
+            (*packet.packet.as_ptr()).dts = 0;
+        }
+
+        let result = unsafe {
+            av_interleaved_write_frame(self.format_context.as_ptr(), packet.packet.as_ptr())
+        };
+        if result < 0 {
+            Err(make_av_error("writing cover image", result))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn add_chapters(&mut self, settings: &OptionalSettings) -> Result<(), Box<dyn Error>> {
+        if settings.chapters.is_empty() {
+            return Ok(());
+        }
+
+        if !(self.is_mov_family() || self.format_name().to_bytes() == b"matroska") {
+            return Err(format!(
+                "Error: the {} container does not support chapters",
+                self.format_name().to_string_lossy()
+            )
+            .into());
+        }
+
+        for (id, (start, end, title)) in settings.chapters.iter().enumerate() {
+            let title = CString::new(title.as_str())?;
+
+            unsafe {
+                // The format context owns its chapters and frees them along with itself, so
+                // they have to be allocated with ffmpeg's allocator.
+                let chapter = av_mallocz(std::mem::size_of::<AVChapter>()) as *mut AVChapter;
+                if chapter.is_null() {
+                    return Err("Error allocating chapter".into());
+                }
+                (*chapter).id = id as i64;
+                (*chapter).time_base = AVRational { num: 1, den: 1000 };
+                (*chapter).start = (start * 1000.0).round() as i64;
+                (*chapter).end = (end * 1000.0).round() as i64;
+                av_dict_set(
+                    &mut (*chapter).metadata,
+                    "title\0".as_ptr() as *const i8,
+                    title.as_ptr(),
+                    0,
+                );
+
+                let result = av_dynarray_add_nofree(
+                    &mut self.format_context.as_mut().chapters as *mut *mut *mut AVChapter
+                        as *mut c_void,
+                    &mut self.format_context.as_mut().nb_chapters as *mut u32 as *mut i32,
+                    chapter as *mut c_void,
+                );
+                if result < 0 {
+                    av_dict_free(&mut (*chapter).metadata);
+                    av_free(chapter as *mut c_void);
+                    return Err(make_av_error("adding chapter", result));
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    fn write_header(&mut self) -> Result<(), Box<dyn Error>> {
+    fn write_header(&mut self, settings: &OptionalSettings) -> Result<(), Box<dyn Error>> {
+        // Otherwise this would only fail when writing the trailer, after the whole video has
+        // been encoded.
+        if !self.is_seekable() && self.requires_seeking(settings) {
+            return Err(concat!(
+                "Error: this container format needs a seekable output. Use a fragmented format ",
+                "instead, e.g. with muxer_option(\"movflags\", \"frag_keyframe+empty_moov\")"
+            )
+            .into());
+        }
+
         let mut opts = ptr::null_mut();
 
+        for (key, value) in &settings.muxer_options {
+            let key = CString::new(key.as_str())?;
+            let value = CString::new(value.as_str())?;
+            unsafe { av_dict_set(&mut opts, key.as_ptr(), value.as_ptr(), 0) };
+        }
+
+        if let Some(brand) = &settings.mp4_brand {
+            let brand = CString::new(brand.as_str())?;
+            unsafe {
+                av_dict_set(
+                    &mut opts,
+                    "brand\0".as_ptr() as *const i8,
+                    brand.as_ptr(),
+                    0,
+                )
+            };
+        }
+
+        // These are options of the format context itself rather than the muxer, but they're
+        // set the same way.
+        if let Some(delta) = settings.max_interleave_delta {
+            unsafe {
+                av_dict_set_int(
+                    &mut opts,
+                    "max_interleave_delta\0".as_ptr() as *const i8,
+                    delta,
+                    0,
+                )
+            };
+        }
+        if let Some(flush) = settings.flush_packets {
+            unsafe {
+                av_dict_set_int(
+                    &mut opts,
+                    "flush_packets\0".as_ptr() as *const i8,
+                    flush as i64,
+                    0,
+                )
+            };
+        }
+
+        unsafe {
+            self.format_context.as_mut().flags |= settings.muxer_flags.as_av();
+            if settings.deterministic {
+                self.format_context.as_mut().flags |= AVFMT_FLAG_BITEXACT as i32;
+            }
+            if let Some(disposition) = settings.video_disposition {
+                self.stream.as_mut().disposition = disposition.as_av();
+            }
+        }
+
         // Safety: the lifetime of the data behind self.context is the same as the
         // lifetime of self, and it is guaranteed to be non-null by the constructor.
         let result = unsafe { avformat_write_header(self.format_context.as_ptr(), &mut opts) };
 
+        // The muxer removes every option it used from the dictionary, so anything left over
+        // wasn't recognized.
+        let unused_option = unsafe {
+            let entry = av_dict_get(
+                opts,
+                "\0".as_ptr() as *const i8,
+                ptr::null(),
+                AV_DICT_IGNORE_SUFFIX,
+            );
+            (!entry.is_null()).then(|| CStr::from_ptr((*entry).key).to_string_lossy().into_owned())
+        };
+
         unsafe { av_dict_free(&mut opts) };
 
         if result < 0 {
             Err(make_av_error("writing header to output file", result))
+        } else if let Some(key) = unused_option {
+            Err(format!("Error: the muxer did not recognize the option {}", key).into())
         } else {
             Ok(())
         }
@@ -205,50 +1424,52 @@ impl OutputStream {
     fn write_trailer(&mut self) -> Result<(), Box<dyn Error>> {
         let result = unsafe { av_write_trailer(self.format_context.as_ptr()) };
 
-        if result < 0 {
+        if result == AVERROR(ENOSPC) {
+            Err(EncoderError::DiskFull.into())
+        } else if result < 0 {
             Err(make_av_error("writing trailer to output file", result))
         } else {
             Ok(())
         }
     }
 
-    pub fn write_frame(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error>> {
-        let frame_to_send = if unsafe { self.encoder_context.codec_context.as_ref().pix_fmt as i32 }
-            != frame.pixel_format()
+    fn write_packet(
+        &self,
+        packet: &mut AVPacketWrapper,
+        codec_time_base: AVRational,
+    ) -> Result<(), Box<dyn Error>> {
+        let (pts, dts) = unsafe { (packet.packet.as_ref().pts, packet.packet.as_ref().dts) };
+        unsafe {
+            av_packet_rescale_ts(
+                packet.packet.as_ptr(),
+                codec_time_base,
+                self.stream.as_ref().time_base,
+            );
+            packet.packet.as_mut().stream_index = self.stream.as_ref().index;
+        }
+
+        // Rescaling gives AV_NOPTS_VALUE, rather than wrapping, if the result doesn't fit.
+        let (rescaled_pts, rescaled_dts) =
+            unsafe { (packet.packet.as_ref().pts, packet.packet.as_ref().dts) };
+        if (pts != AV_NOPTS_VALUE && rescaled_pts == AV_NOPTS_VALUE)
+            || (dts != AV_NOPTS_VALUE && rescaled_dts == AV_NOPTS_VALUE)
         {
-            if self.sws_context.is_none() {
-                self.sws_context = Some(SwsContextWrapper::new(frame, &self.temp_frame)?);
-            }
-            self.sws_context
-                .as_ref()
-                .unwrap()
-                .scale(frame, &mut self.temp_frame, unsafe {
-                    self.encoder_context.codec_context.as_ref().height
-                })?;
+            return Err(EncoderError::TimestampOverflow.into());
+        }
 
-            &mut self.temp_frame
-        } else {
-            frame
+        let res = unsafe {
+            av_interleaved_write_frame(self.format_context.as_ptr(), packet.packet.as_ptr())
         };
-
-        frame_to_send.set_pts(self.next_pts);
-        self.next_pts += 1;
-
-        self.encoder_context.send_frame(frame_to_send)?;
-
-        self.encoder_context
-            .flush(&self.format_context, &mut self.packet, self.stream)?;
-        Ok(())
-    }
-
-    pub fn finish(&mut self) -> Result<(), Box<dyn Error>> {
-        self.encoder_context.finish()?;
-        self.encoder_context
-            .flush(&self.format_context, &mut self.packet, self.stream)?;
-        self.write_trailer()
+        if res == AVERROR(ENOSPC) {
+            Err(EncoderError::DiskFull.into())
+        } else if res < 0 {
+            Err(make_av_error("writing output packet", res))
+        } else {
+            Ok(())
+        }
     }
 }
-impl Drop for OutputStream {
+impl Drop for Muxer {
     fn drop(&mut self) {
         unsafe {
             avio_closep(&mut self.format_context.as_mut().pb);
@@ -278,7 +1499,7 @@ impl AVCodecContextWrapper {
     }
 
     fn send_frame(&self, frame: &Frame) -> Result<(), Box<dyn Error>> {
-        let result = unsafe { avcodec_send_frame(self.codec_context.as_ptr(), frame.as_raw()) };
+        let result = self.try_send_frame(frame);
         if result < 0 {
             Err(make_av_error("sending frame to encoder", result))
         } else {
@@ -286,11 +1507,32 @@ impl AVCodecContextWrapper {
         }
     }
 
+    /// Like [`Self::send_frame`], but returns ffmpeg's error code as is.
+    fn try_send_frame(&self, frame: &Frame) -> i32 {
+        unsafe { avcodec_send_frame(self.codec_context.as_ptr(), frame.as_raw()) }
+    }
+
+    /// Logs `message` through ffmpeg's logging, as coming from the encoder.
+    fn log_warning(&self, message: &str) {
+        // A message can't contain a nul, but it's better to lose the log than to panic.
+        let Ok(message) = CString::new(message) else {
+            return;
+        };
+        unsafe {
+            av_log(
+                self.codec_context.as_ptr() as *mut c_void,
+                AV_LOG_WARNING as i32,
+                "%s\n\0".as_ptr() as *const i8,
+                message.as_ptr(),
+            );
+        }
+    }
+
+    /// Receives every packet the encoder has ready, passing each to `handle_packet`.
     fn flush(
         &self,
-        format_context: &NonNull<AVFormatContext>,
         packet: &mut AVPacketWrapper,
-        stream: NonNull<AVStream>,
+        mut handle_packet: impl FnMut(&mut AVPacketWrapper) -> Result<(), Box<dyn Error>>,
     ) -> Result<(), Box<dyn Error>> {
         let mut res = 0;
         while res >= 0 {
@@ -303,21 +1545,7 @@ impl AVCodecContextWrapper {
                 return Err(make_av_error("encoding a frame", res));
             }
 
-            unsafe {
-                av_packet_rescale_ts(
-                    packet.packet.as_ptr(),
-                    self.codec_context.as_ref().time_base,
-                    stream.as_ref().time_base,
-                );
-                packet.packet.as_mut().stream_index = stream.as_ref().index;
-            }
-
-            res = unsafe {
-                av_interleaved_write_frame(format_context.as_ptr(), packet.packet.as_ptr())
-            };
-            if res < 0 {
-                return Err(make_av_error("writing output packet", res));
-            }
+            handle_packet(packet)?;
         }
 
         Ok(())
@@ -341,6 +1569,16 @@ impl AVPacketWrapper {
         };
         Ok(Self { packet })
     }
+
+    /// A new packet referring to the same data as this one.
+    fn new_ref(&self) -> Result<Self, Box<dyn Error>> {
+        let copy = Self::new()?;
+        let result = unsafe { av_packet_ref(copy.packet.as_ptr(), self.packet.as_ptr()) };
+        if result < 0 {
+            return Err(make_av_error("copying packet", result));
+        }
+        Ok(copy)
+    }
 }
 impl Drop for AVPacketWrapper {
     fn drop(&mut self) {
@@ -352,9 +1590,98 @@ impl Drop for AVPacketWrapper {
 
 struct SwsContextWrapper {
     sws_ctx: NonNull<SwsContext>,
+
+    src_width: i32,
+    src_height: i32,
+    src_format: i32,
 }
 impl SwsContextWrapper {
-    fn new(src: &Frame, dest: &Frame) -> Result<Self, Box<dyn Error>> {
+    fn new(
+        src: &Frame,
+        dest: &Frame,
+        dither: Option<DitherMode>,
+        sharpen: Option<f32>,
+        bitexact: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Without these, the result depends on which SIMD routines the CPU supports.
+        let flags = if bitexact {
+            SWS_BICUBIC | SWS_BITEXACT | SWS_ACCURATE_RND
+        } else {
+            SWS_BICUBIC
+        };
+        // The filter is only used while the context is initialized.
+        let filter = sharpen.map(SwsFilterWrapper::sharpen).transpose()?;
+        let filter_ptr = filter
+            .as_ref()
+            .map_or(ptr::null_mut(), |filter| filter.filter.as_ptr());
+
+        let Some(dither) = dither else {
+            return Self::new_default(src, dest, flags, filter_ptr);
+        };
+
+        // The dithering mode can only be set through the options API, which means setting
+        // everything else that way too.
+        let Some(sws_ctx) = NonNull::new(unsafe { sws_alloc_context() }) else {
+            return Err("Error allocating SwsContext".into());
+        };
+        let options: [(&str, i64); 7] = [
+            ("srcw\0", src.width() as i64),
+            ("srch\0", src.height() as i64),
+            ("src_format\0", src.pixel_format() as i64),
+            ("dstw\0", dest.width() as i64),
+            ("dsth\0", dest.height() as i64),
+            ("dst_format\0", dest.pixel_format() as i64),
+            ("sws_flags\0", flags as i64),
+        ];
+        let mut result = 0;
+        for (name, value) in options {
+            if result >= 0 {
+                result = unsafe {
+                    av_opt_set_int(
+                        sws_ctx.as_ptr() as *mut c_void,
+                        name.as_ptr() as *const i8,
+                        value,
+                        0,
+                    )
+                };
+            }
+        }
+        if result >= 0 {
+            result = unsafe {
+                av_opt_set(
+                    sws_ctx.as_ptr() as *mut c_void,
+                    "sws_dither\0".as_ptr() as *const i8,
+                    dither.as_sws_option().as_ptr() as *const i8,
+                    0,
+                )
+            };
+        }
+        if result >= 0 {
+            result = unsafe { sws_init_context(sws_ctx.as_ptr(), filter_ptr, ptr::null_mut()) };
+        }
+        if result < 0 {
+            unsafe { sws_freeContext(sws_ctx.as_ptr()) };
+            return Err(conversion_error(
+                make_av_error("initializing SwsContext", result),
+                src,
+                dest,
+            ));
+        }
+
+        Ok(Self {
+            sws_ctx,
+            src_width: src.width(),
+            src_height: src.height(),
+            src_format: src.pixel_format(),
+        })
+    }
+
+    fn new_default(
+        src: &Frame,
+        dest: &Frame,
+        flags: i32,
+        filter: *mut SwsFilter,
+    ) -> Result<Self, Box<dyn Error>> {
         let Some(sws_ctx) = NonNull::new(unsafe {
             sws_getContext(
                 src.width(),
@@ -363,19 +1690,80 @@ impl SwsContextWrapper {
                 dest.width(),
                 dest.height(),
                 std::mem::transmute_copy(&dest.pixel_format()),
-                SWS_BICUBIC,
-                ptr::null_mut(),
+                flags,
+                filter,
                 ptr::null_mut(),
                 ptr::null_mut(),
             )
         }) else {
-            return Err("Error initializing SwsContext".into());
+            return Err(conversion_error(
+                "Error initializing SwsContext".into(),
+                src,
+                dest,
+            ));
+        };
+
+        Ok(Self {
+            sws_ctx,
+            src_width: src.width(),
+            src_height: src.height(),
+            src_format: src.pixel_format(),
+        })
+    }
+
+    /// Converts between RGB and YUV with the BT.2020 matrix, rather than swscale's default of
+    /// BT.601.
+    fn use_bt2020_matrix(&self) -> Result<(), Box<dyn Error>> {
+        let mut inv_table = ptr::null_mut();
+        let mut src_range = 0;
+        let mut table = ptr::null_mut();
+        let mut dst_range = 0;
+        let (mut brightness, mut contrast, mut saturation) = (0, 0, 0);
+        // Only the matrices are replaced; the ranges and adjustments are kept as they are.
+        let result = unsafe {
+            sws_getColorspaceDetails(
+                self.sws_ctx.as_ptr(),
+                &mut inv_table,
+                &mut src_range,
+                &mut table,
+                &mut dst_range,
+                &mut brightness,
+                &mut contrast,
+                &mut saturation,
+            )
+        };
+        if result < 0 {
+            return Err(make_av_error("reading SwsContext colorspace", result));
+        }
+
+        let coefficients = unsafe { sws_getCoefficients(SWS_CS_BT2020) };
+        let result = unsafe {
+            sws_setColorspaceDetails(
+                self.sws_ctx.as_ptr(),
+                coefficients,
+                src_range,
+                coefficients,
+                dst_range,
+                brightness,
+                contrast,
+                saturation,
+            )
         };
+        if result < 0 {
+            return Err(make_av_error("setting SwsContext colorspace", result));
+        }
 
-        Ok(Self { sws_ctx })
+        Ok(())
+    }
+
+    /// Returns true if this context was created for source frames like `src`.
+    fn accepts(&self, src: &Frame) -> bool {
+        self.src_width == src.width()
+            && self.src_height == src.height()
+            && self.src_format == src.pixel_format()
     }
 
-    fn scale(&self, src: &Frame, dest: &mut Frame, height: i32) -> Result<(), Box<dyn Error>> {
+    fn scale(&self, src: &Frame, dest: &mut Frame) -> Result<(), Box<dyn Error>> {
         dest.ensure_writeable()?;
 
         unsafe {
@@ -384,7 +1772,7 @@ impl SwsContextWrapper {
                 src.data().as_ptr(),
                 src.linesize().as_ptr(),
                 0,
-                height, // TODO: can this be src.height() ?
+                src.height(),
                 dest.data_mut().as_ptr(),
                 dest.linesize().as_ptr(),
             );
@@ -398,3 +1786,99 @@ impl Drop for SwsContextWrapper {
         unsafe { sws_freeContext(self.sws_ctx.as_ptr()) }
     }
 }
+
+/// Adds the conversion being set up to `err`. With the `compact-errors` feature, `err` is
+/// returned unchanged, so it stays as short as the crate's other errors.
+#[cfg(not(feature = "compact-errors"))]
+fn conversion_error(err: Box<dyn Error>, src: &Frame, dest: &Frame) -> Box<dyn Error> {
+    format!(
+        "{}, converting frames {}",
+        err,
+        describe_conversion(src, dest)
+    )
+    .into()
+}
+
+#[cfg(feature = "compact-errors")]
+fn conversion_error(err: Box<dyn Error>, _src: &Frame, _dest: &Frame) -> Box<dyn Error> {
+    err
+}
+
+/// Describes converting `src` to `dest`, such as "from rgb24 to yuv420p", for error messages.
+#[cfg(not(feature = "compact-errors"))]
+fn describe_conversion(src: &Frame, dest: &Frame) -> String {
+    use ffmpeg_sys_next::av_get_pix_fmt_name;
+
+    let name = |format: AVPixelFormat| {
+        let name = unsafe { av_get_pix_fmt_name(format) };
+        if name.is_null() {
+            format!("unknown pixel format {}", format as i32)
+        } else {
+            unsafe { CStr::from_ptr(name) }
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+
+    format!(
+        "from {} to {}",
+        name(src.av_pixel_format()),
+        name(dest.av_pixel_format())
+    )
+}
+
+struct SwsFilterWrapper {
+    filter: NonNull<SwsFilter>,
+}
+impl SwsFilterWrapper {
+    /// A filter which only sharpens luma, by `amount`.
+    fn sharpen(amount: f32) -> Result<Self, Box<dyn Error>> {
+        let Some(filter) =
+            NonNull::new(unsafe { sws_getDefaultFilter(0.0, 0.0, amount, 0.0, 0.0, 0.0, 0) })
+        else {
+            return Err("Error allocating SwsFilter".into());
+        };
+
+        Ok(Self { filter })
+    }
+}
+impl Drop for SwsFilterWrapper {
+    fn drop(&mut self) {
+        unsafe { sws_freeFilter(self.filter.as_ptr()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_dts_must_increase() {
+        let mut last_dts = None;
+        // B-frames give DTS values before the PTS, and negative ones at the start.
+        check_packet_timestamps(0, -1, &mut last_dts).unwrap();
+        check_packet_timestamps(3, 0, &mut last_dts).unwrap();
+        check_packet_timestamps(1, 1, &mut last_dts).unwrap();
+        assert_eq!(last_dts, Some(1));
+
+        assert!(check_packet_timestamps(2, 1, &mut last_dts).is_err());
+        assert!(check_packet_timestamps(2, 0, &mut last_dts).is_err());
+        assert_eq!(last_dts, Some(1));
+    }
+
+    #[test]
+    fn packet_dts_must_not_be_after_pts() {
+        let mut last_dts = None;
+        assert!(check_packet_timestamps(4, 5, &mut last_dts).is_err());
+        assert_eq!(last_dts, None);
+    }
+
+    #[test]
+    fn packets_without_dts_are_not_checked() {
+        let mut last_dts = Some(5);
+        check_packet_timestamps(1, AV_NOPTS_VALUE, &mut last_dts).unwrap();
+        assert_eq!(last_dts, Some(5));
+        check_packet_timestamps(AV_NOPTS_VALUE, 6, &mut last_dts).unwrap();
+        assert_eq!(last_dts, Some(6));
+    }
+}
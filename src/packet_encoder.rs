@@ -0,0 +1,129 @@
+use std::{error::Error, path::PathBuf, slice, time::Duration};
+
+use ffmpeg_sys_next::{AVPacket, AVRational, AV_PKT_FLAG_KEY};
+
+use crate::{
+    output::{FrameExtras, OutputStream},
+    Frame, PixelFormat, SimpleVideoEncoderBuilder,
+};
+
+/// A single packet of compressed video produced by a [`PacketEncoder`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct EncodedPacket {
+    /// The compressed data. For H.264 and H.265, this is in Annex B format, with the
+    /// parameter sets repeated before each keyframe.
+    pub data: Vec<u8>,
+    /// The presentation timestamp, counted in frames from the start of the video.
+    pub pts: i64,
+    /// The decoding timestamp, counted in frames from the start of the video. This is
+    /// different from `pts` when frames are reordered, for example when B-frames are used.
+    pub dts: i64,
+    /// Whether this packet can be decoded without any previous packets.
+    pub keyframe: bool,
+}
+impl EncodedPacket {
+    pub(crate) fn from_av(packet: &AVPacket) -> Self {
+        let data = if packet.data.is_null() {
+            Vec::new()
+        } else {
+            unsafe { slice::from_raw_parts(packet.data, packet.size as usize) }.to_vec()
+        };
+
+        Self {
+            data,
+            pts: packet.pts,
+            dts: packet.dts,
+            keyframe: packet.flags & AV_PKT_FLAG_KEY as i32 != 0,
+        }
+    }
+}
+
+/// Statistics about a single packet of compressed video, passed to
+/// [`SimpleVideoEncoderBuilder::on_packet`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct PacketStats {
+    /// The size of the packet in bytes.
+    pub size: usize,
+    /// The presentation time of the packet's frame. This counts from a timestamp of 0, so
+    /// with [`SimpleVideoEncoderBuilder::start_pts`] it includes the starting offset.
+    pub pts: Duration,
+    /// Whether the packet is a keyframe.
+    pub keyframe: bool,
+    /// The index of the frame the packet belongs to, counting from 0 for the first frame
+    /// given to the encoder, whatever its timestamp. Skipped duplicates are counted, but frames
+    /// dropped by [`SimpleVideoEncoderBuilder::skip_bad_frames`] are not. Packets may be
+    /// produced out of order when frames are reordered.
+    pub frame_index: u64,
+}
+impl PacketStats {
+    /// `time_base` is the encoder's time base, which the packet's timestamps are in.
+    pub(crate) fn from_av(packet: &AVPacket, time_base: AVRational, frame_index: u64) -> Self {
+        let seconds = packet.pts as f64 * time_base.num as f64 / time_base.den as f64;
+
+        Self {
+            size: packet.size as usize,
+            pts: Duration::from_secs_f64(seconds.max(0.0)),
+            keyframe: packet.flags & AV_PKT_FLAG_KEY as i32 != 0,
+            frame_index,
+        }
+    }
+}
+
+/// An encoder which hands the compressed video back to the caller packet by packet, instead
+/// of writing it into a container. This is useful for sending video over a custom transport.
+///
+/// Create one with [`PacketEncoder::new`], or with [`PacketEncoder::builder`] and
+/// [`SimpleVideoEncoderBuilder::build_packet_encoder`] to change settings.
+pub struct PacketEncoder {
+    width: i32,
+    height: i32,
+
+    output: OutputStream,
+}
+impl PacketEncoder {
+    /// Creates a PacketEncoder with default settings.
+    pub fn new(width: i32, height: i32, framerate: i32) -> Result<Self, Box<dyn Error>> {
+        Self::builder(width, height, framerate).build_packet_encoder()
+    }
+
+    /// Produces a builder which allows specifying additional settings. Settings that only
+    /// affect the container, such as [`SimpleVideoEncoderBuilder::muxer_option`], are
+    /// ignored.
+    pub fn builder(width: i32, height: i32, framerate: i32) -> SimpleVideoEncoderBuilder {
+        SimpleVideoEncoderBuilder::new(PathBuf::new(), width, height, framerate)
+    }
+
+    pub(crate) fn from_output(width: i32, height: i32, output: OutputStream) -> Self {
+        Self {
+            width,
+            height,
+            output,
+        }
+    }
+
+    /// Encodes the frame, and returns any packets the encoder produced. Because encoders
+    /// buffer frames internally, this will often return no packets for the first few frames,
+    /// and the packets it returns may belong to earlier frames.
+    pub fn append_frame(
+        &mut self,
+        frame: &mut Frame,
+    ) -> Result<Vec<EncodedPacket>, Box<dyn Error>> {
+        self.output.write_frame(frame, FrameExtras::default())?;
+        Ok(self.output.take_packets())
+    }
+
+    /// Finishes encoding, and returns the packets for any frames the encoder was still
+    /// holding on to.
+    pub fn finish(mut self) -> Result<Vec<EncodedPacket>, Box<dyn Error>> {
+        self.output.finish()?;
+        Ok(self.output.take_packets())
+    }
+
+    /// Creates a new frame buffer which can be filled with your data and then given to
+    /// [`Self::append_frame`].
+    pub fn new_frame(&self) -> Result<Frame, Box<dyn Error>> {
+        Frame::new(PixelFormat::Rgb24, self.width, self.height)
+    }
+}
@@ -0,0 +1,87 @@
+use std::{error::Error, ffi::CStr, fs::File, io::BufWriter, path::Path};
+
+use ffmpeg_sys_next::av_version_info;
+use serde::Serialize;
+
+use crate::{OptionalSettings, VideoCodec};
+
+/// A description of a finished video and the settings it was encoded with, written as JSON
+/// by [`SimpleVideoEncoderBuilder::write_sidecar`](crate::SimpleVideoEncoderBuilder::write_sidecar).
+///
+/// *Only enabled with the `serde` feature.*
+#[derive(Clone, Debug, Serialize)]
+#[non_exhaustive]
+pub struct EncodeSummary {
+    /// The version string of the ffmpeg libraries the video was encoded with.
+    pub ffmpeg_version: String,
+    /// The name of the ffmpeg encoder used, such as `libx264`.
+    pub encoder: String,
+    /// The width of the video in pixels.
+    pub width: i32,
+    /// The height of the video in pixels.
+    pub height: i32,
+    /// The framerate of the video, in frames per second.
+    pub framerate: f64,
+    /// The CRF the video was encoded with, if any.
+    pub crf: Option<i64>,
+    /// The constant quantizer the video was encoded with, if any.
+    pub qp: Option<u8>,
+    /// The target bitrate in bits/second, if one was set.
+    pub bitrate: Option<i64>,
+    /// The encoder preset, if one was set.
+    pub preset: Option<String>,
+    /// The number of frames given to the encoder.
+    pub frame_count: u64,
+    /// The length of the video in seconds.
+    pub duration_seconds: f64,
+    /// The size of the output in bytes, if it could be determined.
+    pub file_size: Option<u64>,
+    /// The average bitrate actually achieved, in bits/second, if the size is known.
+    pub average_bitrate: Option<f64>,
+}
+impl EncodeSummary {
+    pub(crate) fn new(
+        settings: &OptionalSettings,
+        width: i32,
+        height: i32,
+        framerate: (i32, i32),
+        frame_count: u64,
+        duration_seconds: f64,
+        file_size: Option<u64>,
+    ) -> Self {
+        let ffmpeg_version = unsafe { CStr::from_ptr(av_version_info()) }
+            .to_string_lossy()
+            .into_owned();
+        let encoder = settings
+            .codec
+            .unwrap_or(VideoCodec::H264)
+            .encoder_name()
+            .trim_end_matches('\0')
+            .to_owned();
+        let average_bitrate = file_size
+            .filter(|_| duration_seconds > 0.0)
+            .map(|size| size as f64 * 8.0 / duration_seconds);
+
+        Self {
+            ffmpeg_version,
+            encoder,
+            width,
+            height,
+            framerate: framerate.0 as f64 / framerate.1 as f64,
+            crf: settings.crf,
+            qp: settings.qp,
+            bitrate: settings.bitrate,
+            preset: settings.preset.map(|preset| format!("{:?}", preset)),
+            frame_count,
+            duration_seconds,
+            file_size,
+            average_bitrate,
+        }
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
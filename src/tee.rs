@@ -0,0 +1,94 @@
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use crate::{Frame, SimpleVideoEncoder};
+
+/// An additional destination for a [`TeeEncoder`], such as a file to archive a live stream
+/// to, or a stream to broadcast a recording to.
+#[derive(Clone, Debug)]
+pub struct TeeOutput {
+    pub(crate) target: PathBuf,
+    pub(crate) format_name: Option<String>,
+    pub(crate) streaming: bool,
+    pub(crate) muxer_options: Vec<(String, String)>,
+}
+impl TeeOutput {
+    /// An output file. The container format is detected automatically using the file
+    /// extension.
+    pub fn file<P: AsRef<Path>>(filename: P) -> Self {
+        Self {
+            target: filename.as_ref().to_path_buf(),
+            format_name: None,
+            streaming: false,
+            muxer_options: Vec::new(),
+        }
+    }
+
+    /// A network stream, like [`SimpleVideoEncoder::new_stream`]. The container format
+    /// must be given by name, such as `"flv"` for RTMP.
+    pub fn stream(url: &str, format_name: &str) -> Self {
+        let output = Self {
+            target: PathBuf::from(url),
+            format_name: Some(format_name.to_owned()),
+            streaming: true,
+            muxer_options: Vec::new(),
+        };
+        if format_name == "flv" {
+            // Otherwise the muxer tries to seek back to the header when finishing.
+            output.muxer_option("flvflags", "no_duration_filesize")
+        } else {
+            output
+        }
+    }
+
+    /// Explicitly set the container format, instead of detecting it from the file extension.
+    pub fn container_format(mut self, format_name: impl Into<String>) -> Self {
+        self.format_name = Some(format_name.into());
+        self
+    }
+
+    /// Pass an option to this output's muxer, like
+    /// [`SimpleVideoEncoderBuilder::muxer_option`](crate::SimpleVideoEncoderBuilder::muxer_option).
+    /// Muxer options given to the builder only apply to its own output.
+    pub fn muxer_option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.muxer_options.push((key.into(), value.into()));
+        self
+    }
+}
+
+/// An encoder which encodes each frame once, and writes the result to several outputs at
+/// the same time, such as a file and a live stream.
+///
+/// Create one with [`SimpleVideoEncoder::builder`] and
+/// [`SimpleVideoEncoderBuilder::build_tee`](crate::SimpleVideoEncoderBuilder::build_tee).
+/// The builder's own file is the first output. Every other container setting of the
+/// builder, such as chapters, applies to all of the outputs.
+pub struct TeeEncoder {
+    encoder: SimpleVideoEncoder,
+}
+impl TeeEncoder {
+    pub(crate) fn from_encoder(encoder: SimpleVideoEncoder) -> Self {
+        Self { encoder }
+    }
+
+    /// Encodes the frame and writes it to every output. If writing to any output fails, such
+    /// as when a stream disconnects, an error is returned and the encoder can't continue.
+    pub fn append_frame(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error>> {
+        self.encoder.append_frame(frame)
+    }
+
+    /// Finishes encoding the video and writes any trailer required by each output's
+    /// container format. Every output is finished even if one of them fails, and the first
+    /// error is returned.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.encoder.finish()
+    }
+
+    /// Creates a new frame buffer which can be filled with your data and then given to
+    /// [`Self::append_frame`].
+    pub fn new_frame(&self) -> Result<Frame, Box<dyn Error>> {
+        self.encoder.new_frame()
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+/// Wall-clock time spent encoding each frame, collected when
+/// [`SimpleVideoEncoderBuilder::collect_timing`](crate::SimpleVideoEncoderBuilder::collect_timing)
+/// is enabled. This covers sending the frame to the encoder and writing out the packets it
+/// produced, but not converting the frame to the video's pixel format.
+///
+/// Encoders often buffer several frames before producing any output, so the time for a
+/// single frame can vary a lot; look at the mean and percentiles rather than single frames.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct TimingStats {
+    /// The number of frames that were timed.
+    pub frame_count: usize,
+    /// The fastest frame.
+    pub min: Duration,
+    /// The slowest frame.
+    pub max: Duration,
+    /// The mean time per frame.
+    pub mean: Duration,
+    /// 99% of frames were encoded in this time or less.
+    pub p99: Duration,
+}
+impl TimingStats {
+    pub(crate) fn from_durations(durations: &[Duration]) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+
+        let mut sorted = durations.to_vec();
+        sorted.sort_unstable();
+
+        let total: Duration = sorted.iter().sum();
+        let p99_index = (sorted.len() * 99).div_ceil(100) - 1;
+
+        Some(Self {
+            frame_count: sorted.len(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean: total / sorted.len() as u32,
+            p99: sorted[p99_index],
+        })
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    error::Error,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{EncoderError, Frame, SimpleVideoEncoder, SimpleVideoEncoderBuilder};
+
+/// The number of timestamp ticks per second used by default, giving millisecond precision.
+const DEFAULT_TICKS_PER_SECOND: i32 = 1000;
+
+/// An encoder for frames which arrive at irregular times, such as from screen or window
+/// capture. Each frame is given along with the [`Instant`] it was captured at, and is shown
+/// at that point in the video, relative to the first frame.
+///
+/// Create one with [`VariableRateEncoder::new`], or with [`VariableRateEncoder::builder`] and
+/// [`SimpleVideoEncoderBuilder::build_variable_rate`] to change settings.
+pub struct VariableRateEncoder {
+    encoder: SimpleVideoEncoder,
+
+    // the instant of the first frame, which is shown at the start of the video
+    start: Option<Instant>,
+    // the pts of the first frame, from SimpleVideoEncoderBuilder::start_pts
+    start_pts: i64,
+    last_pts: Option<i64>,
+}
+impl VariableRateEncoder {
+    /// Creates a VariableRateEncoder targeting the specified file name with default settings.
+    pub fn new<P: AsRef<Path>>(
+        filename: P,
+        width: i32,
+        height: i32,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::builder(filename, width, height).build_variable_rate()
+    }
+
+    /// Produces a builder which allows specifying additional settings.
+    ///
+    /// The framerate of the builder sets the precision of the timestamps, and defaults to
+    /// 1000, for millisecond precision. Since frames don't arrive at a fixed rate, settings
+    /// counted in frames, such as [`SimpleVideoEncoderBuilder::set_gop_size`], count frames
+    /// as they arrive; prefer [`SimpleVideoEncoderBuilder::keyframe_interval_seconds`].
+    pub fn builder<P: AsRef<Path>>(
+        filename: P,
+        width: i32,
+        height: i32,
+    ) -> SimpleVideoEncoderBuilder {
+        SimpleVideoEncoder::builder(filename, width, height, DEFAULT_TICKS_PER_SECOND)
+    }
+
+    pub(crate) fn from_encoder(encoder: SimpleVideoEncoder) -> Self {
+        Self {
+            start_pts: encoder.settings.start_pts.unwrap_or(0),
+            encoder,
+            start: None,
+            last_pts: None,
+        }
+    }
+
+    /// Encodes the frame so that it's shown at `instant`. The first frame given is shown at
+    /// the start of the video, which is [`SimpleVideoEncoderBuilder::start_pts`] if set.
+    ///
+    /// Instants must not go backwards. Frames closer together than the timestamp precision
+    /// are moved later by one tick, since no two frames can share a timestamp.
+    pub fn append_frame_at_instant(
+        &mut self,
+        frame: &mut Frame,
+        instant: Instant,
+    ) -> Result<(), Box<dyn Error>> {
+        let start = *self.start.get_or_insert(instant);
+        let elapsed = instant
+            .checked_duration_since(start)
+            .ok_or("Error: frame instant is before the first frame")?;
+        let pts = elapsed_to_pts(
+            elapsed,
+            self.encoder.framerate,
+            self.start_pts,
+            self.last_pts,
+        )?;
+
+        frame.set_pts(pts);
+        self.encoder.append_frame(frame)?;
+        self.last_pts = Some(pts);
+        Ok(())
+    }
+
+    /// Finishes encoding the video and writes any trailer required by the container format.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.encoder.finish()
+    }
+
+    /// Creates a new frame buffer which can be filled with your data and then given to
+    /// [`Self::append_frame_at_instant`].
+    pub fn new_frame(&self) -> Result<Frame, Box<dyn Error>> {
+        self.encoder.new_frame()
+    }
+}
+
+/// Converts the time since the first frame into a timestamp at `framerate` ticks per second,
+/// counting from `start_pts`. The timestamp is at least one tick after `last_pts`.
+fn elapsed_to_pts(
+    elapsed: Duration,
+    framerate: (i32, i32),
+    start_pts: i64,
+    last_pts: Option<i64>,
+) -> Result<i64, Box<dyn Error>> {
+    let (num, den) = framerate;
+    let ticks = (elapsed.as_secs_f64() * num as f64 / den as f64).round();
+    // Casting would silently saturate instead.
+    if ticks >= i64::MAX as f64 {
+        return Err(EncoderError::TimestampOverflow.into());
+    }
+    let mut pts = start_pts
+        .checked_add(ticks as i64)
+        .ok_or(EncoderError::TimestampOverflow)?;
+    if let Some(last_pts) = last_pts {
+        if pts < last_pts {
+            return Err("Error: frame instant is before the previous frame".into());
+        }
+        let after_last = last_pts
+            .checked_add(1)
+            .ok_or(EncoderError::TimestampOverflow)?;
+        pts = pts.max(after_last);
+    }
+    Ok(pts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elapsed_time_is_rounded_to_ticks() {
+        let pts = |micros| elapsed_to_pts(Duration::from_micros(micros), (1000, 1), 0, None);
+        assert_eq!(pts(0).unwrap(), 0);
+        assert_eq!(pts(1_400).unwrap(), 1);
+        assert_eq!(pts(1_600).unwrap(), 2);
+        assert_eq!(pts(2_000_000).unwrap(), 2000);
+
+        // 30000/1001 ticks per second
+        let pts = elapsed_to_pts(Duration::from_secs(1001), (30000, 1001), 0, None);
+        assert_eq!(pts.unwrap(), 30000);
+    }
+
+    #[test]
+    fn timestamps_count_from_start_pts() {
+        let pts = elapsed_to_pts(Duration::ZERO, (1000, 1), 500, None);
+        assert_eq!(pts.unwrap(), 500);
+        let pts = elapsed_to_pts(Duration::from_millis(20), (1000, 1), 500, Some(500));
+        assert_eq!(pts.unwrap(), 520);
+        assert!(elapsed_to_pts(Duration::from_millis(1), (1000, 1), i64::MAX, None).is_err());
+    }
+
+    #[test]
+    fn close_frames_are_moved_apart() {
+        let pts = elapsed_to_pts(Duration::from_micros(5_200), (1000, 1), 0, Some(5));
+        assert_eq!(pts.unwrap(), 6);
+        let pts = elapsed_to_pts(Duration::from_millis(9), (1000, 1), 0, Some(5));
+        assert_eq!(pts.unwrap(), 9);
+    }
+
+    #[test]
+    fn timestamps_must_not_go_backwards_or_overflow() {
+        assert!(elapsed_to_pts(Duration::from_millis(4), (1000, 1), 0, Some(5)).is_err());
+        assert!(elapsed_to_pts(Duration::MAX, (1000, 1), 0, None).is_err());
+        assert!(elapsed_to_pts(Duration::ZERO, (1000, 1), 0, Some(i64::MAX)).is_err());
+    }
+}